@@ -14,7 +14,17 @@ import View exposing (view)
 
 urlToView : Url.Url -> CurrentView
 urlToView url =
-    url
+    -- only look at the end of the path, the UI can be served under a prefix
+    { url
+        | path =
+            String.split "/" url.path
+                |> List.filter (not << String.isEmpty)
+                |> List.reverse
+                |> List.take 2
+                |> List.reverse
+                |> String.join "/"
+                |> (++) "/"
+    }
         |> Url.parse urlParser
         |> Maybe.withDefault TheaterView
 
@@ -50,10 +60,10 @@ update msg model =
                 url =
                     case view of
                         TheaterView ->
-                            "/ui/theater"
+                            "ui/theater"
 
                         HospitalView ->
-                            "/ui/hospital"
+                            "ui/hospital"
             in
             ( model, Nav.pushUrl model.navKey url )
 
@@ -2,6 +2,7 @@ module MainApi exposing (cleanAndScan, getInit, refreshMovies, setTags, update)
 
 import Browser exposing (UrlRequest(..))
 import Http
+import Json.Decode
 import Json.Encode
 import List.Extra
 import Model exposing (ApiMsgs(..), CurrentView(..), HospitalMsgs(..), Model, Msg(..), SortBy(..))
@@ -17,7 +18,7 @@ setTags movie_id tags =
         [ Http.request
             { method = "PUT"
             , headers = []
-            , url = "/api/movies/" ++ String.fromInt movie_id
+            , url = "api/movies/" ++ String.fromInt movie_id
             , body = Http.jsonBody (Json.Encode.list Json.Encode.string tags)
             , expect = Http.expectString (\msg -> ApiMsg (DataStringReceived msg))
             , timeout = Nothing
@@ -36,7 +37,7 @@ refreshMovies movie_ids =
                     Http.request
                         { method = "DELETE"
                         , headers = []
-                        , url = "/api/movies/" ++ String.fromInt id
+                        , url = "api/movies/" ++ String.fromInt id
                         , body = Http.emptyBody
                         , expect = Http.expectString (\msg -> ApiMsg (DataStringReceived msg))
                         , timeout = Nothing
@@ -61,33 +62,84 @@ updateMovies =
     Http.task
         { method = "PUT"
         , headers = []
-        , url = "/api/movies"
+        , url = "api/movies"
         , body = Http.emptyBody
         , resolver = Http.stringResolver <| Model.handleJsonResponse <| Model.movieListDecoder
         , timeout = Nothing
         }
 
 
+getMovieList : Task.Task Http.Error (List Model.Movie)
+getMovieList =
+    Http.task
+        { method = "GET"
+        , headers = []
+        , url = "api/movies"
+        , body = Http.emptyBody
+        , resolver = Http.stringResolver <| Model.handleJsonResponse <| Model.movieListDecoder
+        , timeout = Nothing
+        }
+
+
+waitForJob : Int -> Task.Task Http.Error ()
+waitForJob job_id =
+    Process.sleep 5000
+        |> Task.andThen
+            (\_ ->
+                Http.task
+                    { method = "GET"
+                    , headers = []
+                    , url = "api/jobs/" ++ String.fromInt job_id
+                    , body = Http.emptyBody
+                    , resolver =
+                        Http.stringResolver <|
+                            Model.handleJsonResponse <|
+                                Json.Decode.map2 Tuple.pair
+                                    (Json.Decode.field "status" Json.Decode.string)
+                                    (Json.Decode.field "error" (Json.Decode.nullable Json.Decode.string))
+                    , timeout = Nothing
+                    }
+            )
+        |> Task.andThen
+            (\( status, error ) ->
+                case status of
+                    "running" ->
+                        waitForJob job_id
+
+                    "failed" ->
+                        Task.fail (Http.BadBody ("job failed: " ++ Maybe.withDefault "unknown error" error))
+
+                    "cancelled" ->
+                        Task.fail (Http.BadBody "job cancelled")
+
+                    _ ->
+                        Task.succeed ()
+            )
+
+
 cleanAndScan : Cmd Msg
 cleanAndScan =
     Cmd.batch
         [ Task.perform (\t -> HospitalMsg (SetStartLoadingTime t)) Time.now
-        , Http.request
-            { method = "DELETE"
-            , headers = []
-            , url = "/api/movies"
-            , body = Http.emptyBody
-            , expect = Http.expectJson (\json -> ApiMsg (DataMovieListReceived json)) Model.movieListDecoder
-            , timeout = Nothing
-            , tracker = Nothing
-            }
+        , Task.attempt (\json -> ApiMsg (DataMovieListReceived json))
+            (Http.task
+                { method = "DELETE"
+                , headers = []
+                , url = "api/movies"
+                , body = Http.emptyBody
+                , resolver = Http.stringResolver <| Model.handleJsonResponse <| Json.Decode.field "id" Json.Decode.int
+                , timeout = Nothing
+                }
+                |> Task.andThen waitForJob
+                |> Task.andThen (\_ -> getMovieList)
+            )
         ]
 
 
 getMovies : Cmd Msg
 getMovies =
     Http.get
-        { url = "/api/movies"
+        { url = "api/movies"
         , expect = Http.expectJson (\json -> ApiMsg (DataMovieListReceived json)) Model.movieListDecoder
         }
 
@@ -95,7 +147,7 @@ getMovies =
 getConfig : Cmd Msg
 getConfig =
     Http.get
-        { url = "/api/config"
+        { url = "api/config"
         , expect = Http.expectJson (\json -> ApiMsg (DataConfigReceived json)) Model.configDecoder
         }
 
@@ -103,7 +155,7 @@ getConfig =
 getDuplicates : Cmd Msg
 getDuplicates =
     Http.get
-        { url = "/api/errors/duplicates"
+        { url = "api/errors/duplicates"
         , expect = Http.expectJson (\json -> ApiMsg (DataDuplicatesReceived json)) Model.movieListDecoder
         }
 
@@ -111,7 +163,7 @@ getDuplicates =
 getRecognitionErrors : Cmd Msg
 getRecognitionErrors =
     Http.get
-        { url = "/api/errors/recognition"
+        { url = "api/errors/recognition"
         , expect = Http.expectJson (\json -> ApiMsg (DataRecognitionErrorsReceived json)) Model.movieListDecoder
         }
 
@@ -119,7 +171,7 @@ getRecognitionErrors =
 getMissingMovies : Cmd Msg
 getMissingMovies =
     Http.get
-        { url = "/api/errors/missing"
+        { url = "api/errors/missing"
         , expect = Http.expectJson (\json -> ApiMsg (DataMissingMoviesReceived json)) Model.pathListDecoder
         }
 
@@ -0,0 +1,375 @@
+use actix_web::dev::ServiceRequest;
+use actix_web::{http, web, HttpMessage, HttpRequest, HttpResponse};
+use serde::Deserialize;
+use tracing::{event, instrument, Level};
+
+use crate::auth::{AuthConfig, Identity, Role, SESSIONS};
+use crate::CONFIG;
+
+pub const SESSION_COOKIE: &str = "kodi-helper-session";
+
+#[derive(Deserialize)]
+pub struct Credentials {
+    pub username: String,
+    pub password: String,
+}
+
+impl std::fmt::Debug for Credentials {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Credentials")
+            .field("username", &self.username)
+            .finish()
+    }
+}
+
+/// Find who is making the request, from its session cookie or its bearer token
+fn identify<R: HttpMessage>(req: &R, auth: &AuthConfig) -> Option<Identity> {
+    if let Some(identity) = req
+        .cookie(SESSION_COOKIE)
+        .and_then(|cookie| SESSIONS.get(cookie.value()))
+    {
+        return Some(identity);
+    }
+    let token = req
+        .headers()
+        .get(http::header::AUTHORIZATION)?
+        .to_str()
+        .ok()?
+        .strip_prefix("Bearer ")?;
+    auth.check_token(token)
+}
+
+/// Reading is allowed to viewers, anything else needs an admin
+fn required_role(method: &http::Method) -> Role {
+    match *method {
+        http::Method::GET | http::Method::HEAD => Role::Viewer,
+        _ => Role::Admin,
+    }
+}
+
+/// Paths anyone can reach, every other path needs a login or a token
+const PUBLIC_PATHS: &[&str] = &["/", "/api/login", "/api/logout"];
+/// Folders anyone can reach: the UI files, and the UI pages which all serve the same index
+const PUBLIC_FOLDERS: &[&str] = &["/static/", "/ui/"];
+
+/// Check that a request is allowed, returning the response to send otherwise. Everything is
+/// allowed when no auth is configured. Otherwise only the UI and the login routes are public,
+/// `prefix` being the prefix the routes are mounted under.
+pub fn check_access(req: &ServiceRequest, prefix: &str) -> Result<(), HttpResponse> {
+    match CONFIG.read().unwrap().auth.as_ref() {
+        Some(auth) => authorize(req, prefix, auth),
+        None => Ok(()),
+    }
+}
+
+/// Check that a request is allowed by the access control
+fn authorize(req: &ServiceRequest, prefix: &str, auth: &AuthConfig) -> Result<(), HttpResponse> {
+    let (path, mounted) = match req.path().strip_prefix(prefix) {
+        Some("") => ("/", true),
+        Some(path) => (path, true),
+        None => (req.path(), false),
+    };
+    if mounted
+        && (PUBLIC_PATHS.contains(&path)
+            || PUBLIC_FOLDERS.iter().any(|folder| path.starts_with(folder)))
+    {
+        return Ok(());
+    }
+
+    match identify(req, auth) {
+        None => Err(HttpResponse::Unauthorized().json(serde_json::json!({
+            "error": "unauthorized",
+            "message": "login or use an API token",
+        }))),
+        Some(identity) if identity.role < required_role(req.method()) => {
+            event!(
+                Level::INFO,
+                "{} is not allowed to {} {}",
+                identity.name,
+                req.method(),
+                path
+            );
+            Err(HttpResponse::Forbidden().json(serde_json::json!({
+                "error": "forbidden",
+                "message": "this needs the admin role",
+            })))
+        }
+        Some(_) => Ok(()),
+    }
+}
+
+#[instrument(level = "info")]
+#[allow(clippy::async_yields_async)]
+pub async fn login(credentials: web::Json<Credentials>) -> HttpResponse {
+    let auth = if let Some(auth) = CONFIG.read().unwrap().auth.clone() {
+        auth
+    } else {
+        return HttpResponse::NotFound().json("auth is not enabled");
+    };
+    let duration = std::time::Duration::from_secs(auth.session_duration);
+    let credentials = credentials.into_inner();
+
+    // bcrypt is slow on purpose, keep it out of the executor
+    match web::block(move || {
+        auth.check_password(&credentials.username, &credentials.password)
+            .ok_or(())
+    })
+    .await
+    {
+        Ok(identity) => {
+            let session = SESSIONS.create(identity.clone(), duration);
+            let server = CONFIG.read().unwrap().server.clone();
+            HttpResponse::Ok()
+                .cookie(
+                    http::Cookie::build(SESSION_COOKIE, session)
+                        .path(format!("{}/", server.route_prefix()))
+                        .secure(server.tls.is_some())
+                        .http_only(true)
+                        .same_site(actix_web::cookie::SameSite::Strict)
+                        .finish(),
+                )
+                .json(identity)
+        }
+        Err(_) => HttpResponse::Unauthorized().json(serde_json::json!({
+            "error": "unauthorized",
+            "message": "invalid username or password",
+        })),
+    }
+}
+
+#[instrument(level = "info")]
+pub fn logout(req: HttpRequest) -> HttpResponse {
+    let mut response = HttpResponse::Ok();
+    if let Some(cookie) = req.cookie(SESSION_COOKIE) {
+        SESSIONS.remove(cookie.value());
+        let prefix = CONFIG.read().unwrap().server.route_prefix();
+        response.del_cookie(
+            &http::Cookie::build(SESSION_COOKIE, "")
+                .path(format!("{}/", prefix))
+                .finish(),
+        );
+    }
+    response.json("ok")
+}
+
+/// Who is logged in, `null` when auth is not enabled
+#[instrument(level = "info")]
+pub fn get_session(req: HttpRequest) -> HttpResponse {
+    match CONFIG.read().unwrap().auth.as_ref() {
+        Some(auth) => HttpResponse::Ok().json(identify(&req, auth)),
+        None => HttpResponse::Ok().json(serde_json::Value::Null),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use actix_web::http::{Method, StatusCode};
+    use actix_web::test::TestRequest;
+    use sha2::Digest;
+
+    fn token(name: &str, token: &str, role: Role) -> crate::auth::ApiToken {
+        crate::auth::ApiToken {
+            name: name.to_string(),
+            token_sha256: format!("{:x}", sha2::Sha256::digest(token.as_bytes())),
+            role,
+        }
+    }
+
+    fn auth() -> AuthConfig {
+        AuthConfig {
+            users: vec![],
+            tokens: vec![
+                token("viewer", "viewer-token", Role::Viewer),
+                token("admin", "admin-token", Role::Admin),
+            ],
+            session_duration: 60,
+        }
+    }
+
+    fn status(result: Result<(), HttpResponse>) -> StatusCode {
+        result.map_or_else(|response| response.status(), |_| StatusCode::OK)
+    }
+
+    #[test]
+    fn access_by_path_method_and_role() {
+        let viewer = Some("Bearer viewer-token");
+        let admin = Some("Bearer admin-token");
+        let table = [
+            // public paths and folders, mounted under the prefix only
+            ("", Method::GET, "/", None, StatusCode::OK),
+            ("", Method::POST, "/api/login", None, StatusCode::OK),
+            ("", Method::POST, "/api/logout", None, StatusCode::OK),
+            ("", Method::GET, "/static/main.js", None, StatusCode::OK),
+            ("", Method::GET, "/ui/movies", None, StatusCode::OK),
+            ("", Method::GET, "/static", None, StatusCode::UNAUTHORIZED),
+            (
+                "",
+                Method::GET,
+                "/staticfile",
+                None,
+                StatusCode::UNAUTHORIZED,
+            ),
+            (
+                "",
+                Method::GET,
+                "/api/session",
+                None,
+                StatusCode::UNAUTHORIZED,
+            ),
+            ("/kh", Method::GET, "/kh", None, StatusCode::OK),
+            ("/kh", Method::GET, "/kh/", None, StatusCode::OK),
+            (
+                "/kh",
+                Method::GET,
+                "/kh/static/main.js",
+                None,
+                StatusCode::OK,
+            ),
+            ("/kh", Method::POST, "/kh/api/login", None, StatusCode::OK),
+            (
+                "/kh",
+                Method::GET,
+                "/static/main.js",
+                None,
+                StatusCode::UNAUTHORIZED,
+            ),
+            (
+                "/kh",
+                Method::POST,
+                "/api/login",
+                None,
+                StatusCode::UNAUTHORIZED,
+            ),
+            (
+                "/kh",
+                Method::GET,
+                "/kh/api/movies",
+                None,
+                StatusCode::UNAUTHORIZED,
+            ),
+            // viewers read, admins do anything
+            ("", Method::GET, "/api/movies", viewer, StatusCode::OK),
+            ("", Method::HEAD, "/api/movies", viewer, StatusCode::OK),
+            (
+                "",
+                Method::PUT,
+                "/api/movies",
+                viewer,
+                StatusCode::FORBIDDEN,
+            ),
+            ("", Method::POST, "/api/jobs", viewer, StatusCode::FORBIDDEN),
+            (
+                "",
+                Method::DELETE,
+                "/api/jobs/1",
+                viewer,
+                StatusCode::FORBIDDEN,
+            ),
+            ("", Method::GET, "/api/movies", admin, StatusCode::OK),
+            ("", Method::PUT, "/api/movies", admin, StatusCode::OK),
+            ("", Method::DELETE, "/api/jobs/1", admin, StatusCode::OK),
+            ("/kh", Method::GET, "/kh/api/movies", viewer, StatusCode::OK),
+            (
+                "/kh",
+                Method::PUT,
+                "/kh/api/config",
+                viewer,
+                StatusCode::FORBIDDEN,
+            ),
+            ("/kh", Method::PUT, "/kh/api/config", admin, StatusCode::OK),
+            // bad credentials
+            (
+                "",
+                Method::GET,
+                "/api/movies",
+                Some("Bearer wrong"),
+                StatusCode::UNAUTHORIZED,
+            ),
+            (
+                "",
+                Method::GET,
+                "/api/movies",
+                Some("Bearer "),
+                StatusCode::UNAUTHORIZED,
+            ),
+            (
+                "",
+                Method::GET,
+                "/api/movies",
+                Some("viewer-token"),
+                StatusCode::UNAUTHORIZED,
+            ),
+            (
+                "",
+                Method::GET,
+                "/api/movies",
+                Some("Basic dmlld2VyOg=="),
+                StatusCode::UNAUTHORIZED,
+            ),
+        ];
+        let auth = auth();
+        for (prefix, method, path, authorization, expected) in table.iter() {
+            let mut request = TestRequest::default().method(method.clone()).uri(path);
+            if let Some(authorization) = authorization {
+                request = request.header(http::header::AUTHORIZATION, *authorization);
+            }
+            assert_eq!(
+                status(authorize(&request.to_srv_request(), prefix, &auth)),
+                *expected,
+                "{} {} under {:?} with {:?}",
+                method,
+                path,
+                prefix,
+                authorization
+            );
+        }
+    }
+
+    #[test]
+    fn access_by_session() {
+        let auth = auth();
+        let viewer = Identity {
+            name: "viewer".to_string(),
+            role: Role::Viewer,
+        };
+        let session = SESSIONS.create(viewer.clone(), std::time::Duration::from_secs(60));
+        let expired = SESSIONS.create(viewer, std::time::Duration::from_secs(0));
+        let table = [
+            (Method::GET, Some(session.as_str()), StatusCode::OK),
+            (Method::PUT, Some(session.as_str()), StatusCode::FORBIDDEN),
+            (
+                Method::GET,
+                Some(expired.as_str()),
+                StatusCode::UNAUTHORIZED,
+            ),
+            (Method::GET, Some("unknown"), StatusCode::UNAUTHORIZED),
+            (Method::GET, None, StatusCode::UNAUTHORIZED),
+        ];
+        for (method, cookie, expected) in table.iter() {
+            let mut request = TestRequest::default()
+                .method(method.clone())
+                .uri("/api/movies");
+            if let Some(cookie) = cookie {
+                request = request.cookie(http::Cookie::new(SESSION_COOKIE, *cookie));
+            }
+            assert_eq!(
+                status(authorize(&request.to_srv_request(), "", &auth)),
+                *expected,
+                "{} with {:?}",
+                method,
+                cookie
+            );
+        }
+
+        SESSIONS.remove(&session);
+        let request = TestRequest::default()
+            .uri("/api/movies")
+            .cookie(http::Cookie::new(SESSION_COOKIE, session.as_str()))
+            .to_srv_request();
+        assert_eq!(
+            status(authorize(&request, "", &auth)),
+            StatusCode::UNAUTHORIZED
+        );
+    }
+}
@@ -1,9 +1,60 @@
-use actix_web::HttpResponse;
-use tracing::instrument;
+use actix_web::{web, HttpResponse};
+use tracing::{event, instrument, Level};
 
-use crate::CONFIG;
+use crate::config::CONFIG_FILE;
+use crate::events::{Event, EVENTS};
+use crate::kodi_rpc::KodiClients;
+use crate::{Config, CONFIG};
 
 #[instrument(level = "info")]
 pub fn get_config() -> HttpResponse {
     HttpResponse::Ok().json(CONFIG.read().unwrap().clone())
 }
+
+/// Validate, save and apply a new configuration, keeping the server settings. The settings that
+/// differ from the configuration file are saved in its overrides file, whose path is returned with
+/// the new configuration, and the file itself is left as written.
+#[instrument(skip(new_config, kodi_clients), level = "info")]
+#[allow(clippy::async_yields_async)]
+pub async fn update_config(
+    new_config: web::Json<Config>,
+    kodi_clients: web::Data<std::sync::RwLock<KodiClients>>,
+) -> HttpResponse {
+    let mut config = new_config.into_inner();
+    crate::config::keep_secrets(&mut config, &CONFIG.read().unwrap());
+    // server settings are only read from the configuration file
+    config.server = crate::config::FILE_SERVER
+        .read()
+        .unwrap()
+        .clone()
+        .unwrap_or_else(|| CONFIG.read().unwrap().server.clone());
+    if let Err(problems) = crate::config::validate(&config) {
+        return HttpResponse::BadRequest().json(serde_json::json!({
+            "error": "invalid_config",
+            "errors": problems,
+        }));
+    }
+
+    let path = CONFIG_FILE.read().unwrap().clone();
+    let overrides = path.as_deref().map(crate::config::overrides_path);
+    if let Some(path) = path {
+        let to_save = config.clone();
+        let saved =
+            web::block(move || crate::config::save(&path, &to_save).map_err(|err| err.to_string()))
+                .await;
+        if let Err(err) = saved {
+            event!(Level::ERROR, "error saving configuration: {}", err);
+            return HttpResponse::InternalServerError().json(serde_json::json!({
+                "error": "save_failed",
+                "message": err.to_string(),
+            }));
+        }
+    }
+    crate::config::apply(config, &kodi_clients);
+    EVENTS.send(Event::ConfigReloaded { errors: vec![] });
+
+    HttpResponse::Ok().json(serde_json::json!({
+        "config": CONFIG.read().unwrap().clone(),
+        "overrides": overrides,
+    }))
+}
@@ -1,144 +1,276 @@
 use actix_web::{web, HttpResponse};
 use tracing::{event, instrument, Level};
 
-use crate::{kodi_rpc, Movie, CONFIG};
+use crate::kodi_rpc::{KodiClients, KodiRpcError};
+use crate::{hospital, Kodi, MovieLists, MusicLibraries, TvShowLists, CONFIG};
 
-#[instrument(skip(movie_list), level = "info")]
+#[instrument(skip(movie_lists, kodi_clients), level = "info")]
 pub async fn get_unrecognized_movies(
-    movie_list: web::Data<std::sync::RwLock<Vec<Movie>>>,
+    movie_lists: web::Data<std::sync::RwLock<MovieLists>>,
+    kodi_clients: web::Data<std::sync::RwLock<KodiClients>>,
+    kodi: Kodi,
+) -> Result<HttpResponse, KodiRpcError> {
+    let config = CONFIG.read().unwrap().clone();
+    let kodi_rpc = kodi_clients.read().unwrap().rpc(&kodi)?;
+    let movies = movie_lists
+        .read()
+        .unwrap()
+        .get(&kodi.name)
+        .cloned()
+        .unwrap_or_default();
+    let missing = hospital::find_missing_movies(&kodi_rpc, &movies, &config).await?;
+
+    event!(Level::INFO, "found missing movies: {}", missing.len());
+
+    Ok(HttpResponse::Ok().json(missing))
+}
+
+/// Movies of the library whose files no longer exist
+#[instrument(skip(movie_lists, kodi_clients), level = "info")]
+pub async fn get_orphan_movies(
+    movie_lists: web::Data<std::sync::RwLock<MovieLists>>,
+    kodi_clients: web::Data<std::sync::RwLock<KodiClients>>,
+    kodi: Kodi,
+) -> Result<HttpResponse, KodiRpcError> {
+    let config = CONFIG.read().unwrap().clone();
+    let kodi_rpc = kodi_clients.read().unwrap().rpc(&kodi)?;
+    let movies = movie_lists
+        .read()
+        .unwrap()
+        .get(&kodi.name)
+        .cloned()
+        .unwrap_or_default();
+    let orphans = hospital::find_orphans(&kodi_rpc, &movies, &config).await?;
+
+    event!(Level::INFO, "found orphan movies: {}", orphans.len());
+    Ok(HttpResponse::Ok().json(orphans))
+}
+
+/// Run every check of the movies, TV shows and music library, the files of the movie sources and
+/// of the TV shows directory and the movies of the other instances being fetched first. Orphans
+/// are left to [`get_orphan_movies`], as they need a check of every file.
+#[instrument(
+    skip(movie_lists, tvshow_lists, music_libraries, kodi_clients),
+    level = "info"
+)]
+pub async fn get_hospital_report(
+    movie_lists: web::Data<std::sync::RwLock<MovieLists>>,
+    tvshow_lists: web::Data<std::sync::RwLock<TvShowLists>>,
+    music_libraries: web::Data<std::sync::RwLock<MusicLibraries>>,
+    kodi_clients: web::Data<std::sync::RwLock<KodiClients>>,
+    kodi: Kodi,
+) -> Result<HttpResponse, KodiRpcError> {
+    let config = CONFIG.read().unwrap().clone();
+    let kodi_rpc = kodi_clients.read().unwrap().rpc(&kodi)?;
+    let files = hospital::list_movie_files(&kodi_rpc, &config).await?;
+    let episode_files = hospital::list_episode_files(&kodi_rpc, &config).await?;
+    let tvshows = tvshow_lists
+        .read()
+        .unwrap()
+        .get(&kodi.name)
+        .cloned()
+        .unwrap_or_default();
+    let music_library = music_libraries
+        .read()
+        .unwrap()
+        .get(&kodi.name)
+        .cloned()
+        .unwrap_or_default();
+    let movie_lists = movie_lists.read().unwrap().clone();
+    let movies = movie_lists.get(&kodi.name).cloned().unwrap_or_default();
+
+    let mut checks = hospital::movie_checks(hospital::source_parsers(&config)?);
+    checks.push(Box::new(hospital::MissingFiles { files }));
+    checks.push(Box::new(hospital::Divergences {
+        kodi: kodi.name.clone(),
+        movie_lists,
+    }));
+    checks.extend(hospital::tvshow_checks(tvshows, episode_files));
+    checks.extend(hospital::music_checks(music_library));
+    let report = hospital::report(&checks, &movies, &config);
+
+    event!(Level::INFO, "found issues: {}", report.count);
+    Ok(HttpResponse::Ok().json(report))
+}
+
+#[instrument(skip(movie_lists), level = "info")]
+pub async fn get_duplicate_movies_list(
+    movie_lists: web::Data<std::sync::RwLock<MovieLists>>,
+    kodi: Kodi,
+) -> Result<HttpResponse, KodiRpcError> {
+    let parser = hospital::source_parsers(&CONFIG.read().unwrap())?;
+    let readable_movie_list = movie_lists
+        .read()
+        .unwrap()
+        .get(&kodi.name)
+        .cloned()
+        .unwrap_or_default();
+    let dups = hospital::find_duplicates(&readable_movie_list, &parser);
+
+    event!(Level::INFO, "found duplicates: {}", dups.len());
+    Ok(HttpResponse::Ok().json(dups))
+}
+
+/// Copies of duplicated movies ranked by quality, with the one to keep
+#[instrument(skip(movie_lists, kodi_clients), level = "info")]
+pub async fn get_upgrade_candidates(
+    movie_lists: web::Data<std::sync::RwLock<MovieLists>>,
+    kodi_clients: web::Data<std::sync::RwLock<KodiClients>>,
+    kodi: Kodi,
+) -> Result<HttpResponse, KodiRpcError> {
+    let config = CONFIG.read().unwrap().clone();
+    let kodi_rpc = kodi_clients.read().unwrap().rpc(&kodi)?;
+    let movies = movie_lists
+        .read()
+        .unwrap()
+        .get(&kodi.name)
+        .cloned()
+        .unwrap_or_default();
+    let candidates = hospital::rank_duplicates(&kodi_rpc, &movies, &config).await?;
+
+    event!(
+        Level::INFO,
+        "found upgrade candidates: {}",
+        candidates.len()
+    );
+    Ok(HttpResponse::Ok().json(candidates))
+}
+
+#[instrument(skip(movie_lists), level = "info")]
+pub async fn get_recognition_errors_list(
+    movie_lists: web::Data<std::sync::RwLock<MovieLists>>,
+    kodi: Kodi,
+) -> Result<HttpResponse, KodiRpcError> {
+    let config = CONFIG.read().unwrap().clone();
+    let parser = hospital::source_parsers(&config)?;
+    let readable_movie_list = movie_lists
+        .read()
+        .unwrap()
+        .get(&kodi.name)
+        .cloned()
+        .unwrap_or_default();
+    let diffs = hospital::find_recognition_errors(&readable_movie_list, &parser, &config);
+
+    event!(Level::INFO, "found recognition errors: {}", diffs.len());
+    Ok(HttpResponse::Ok().json(diffs))
+}
+
+#[instrument(skip(movie_lists), level = "info")]
+pub fn get_divergent_movies_list(
+    movie_lists: web::Data<std::sync::RwLock<MovieLists>>,
 ) -> HttpResponse {
     let config = CONFIG.read().unwrap();
-    let ignored_patterns = config
-        .filepatterns_to_ignore
-        .iter()
-        .map(|pattern| regex::Regex::new(pattern).unwrap())
-        .collect::<Vec<_>>();
-    if let Ok(files) = kodi_rpc::KodiRPC::new(&config.kodis[0].url)
-        .get_directory(&config.movies_directory)
-        .await
-    {
-        let known_files: Vec<_> = movie_list
-            .read()
-            .unwrap()
-            .iter()
-            .map(|m| m.path.clone())
-            .collect();
-
-        let missing = files
-            .into_iter()
-            .filter(|f| {
-                for pattern in ignored_patterns.iter() {
-                    if pattern.is_match(&f.path) {
-                        return false;
-                    }
-                }
-                return true;
-            })
-            .filter(|f| !known_files.contains(&f.path))
-            .collect::<Vec<_>>();
-
-        event!(Level::INFO, "found missing movies: {}", missing.len());
-
-        HttpResponse::Ok().json(missing)
-    } else {
-        HttpResponse::InternalServerError().json("err")
-    }
+    let divergences = crate::find_divergences(&movie_lists.read().unwrap(), &config.kodis);
+
+    event!(Level::INFO, "found divergences: {}", divergences.len());
+    HttpResponse::Ok().json(divergences)
 }
 
-#[instrument(skip(movie_list), level = "info")]
-pub fn get_duplicate_movies_list(
-    movie_list: web::Data<std::sync::RwLock<Vec<Movie>>>,
+#[instrument(skip(tvshow_lists), level = "info")]
+pub fn get_missing_episodes_list(
+    tvshow_lists: web::Data<std::sync::RwLock<TvShowLists>>,
+    kodi: Kodi,
 ) -> HttpResponse {
-    let readable_movie_list = movie_list.read().unwrap().clone();
-    let config = CONFIG.read().unwrap();
-    let movie_pattern = regex::Regex::new(&format!(
-        "^{}{}",
-        config.movies_directory, config.movie_pattern
-    ))
-    .unwrap();
-
-    let dups: Vec<Movie> = readable_movie_list
-        .into_iter()
-        .map(|movie| {
-            (
-                movie.clone(),
-                movie_pattern
-                    .captures(&movie.path)
-                    .and_then(|c| c.name("year"))
-                    .map(|m| m.as_str().to_string()),
-            )
-        })
-        .fold(
-            std::collections::HashMap::new(),
-            |mut map, (movie, year)| {
-                map.entry(movie.title.clone())
-                    .or_insert_with(|| Vec::new())
-                    .push((movie, year));
-                map
-            },
-        )
-        .iter()
-        .filter(|(_, m_y)| {
-            let years: Vec<Option<String>> = m_y.iter().map(|(_, y)| y.clone()).collect();
-            years.len() != 1
-                && years.len()
-                    != years
-                        .iter()
-                        .filter_map(|y| y.clone())
-                        .fold(std::collections::HashSet::new(), |mut set, year| {
-                            set.insert(year);
-                            set
-                        })
-                        .len()
-        })
-        .map(|(_, m_y)| m_y.iter().map(|(m, _)| m.clone()).collect::<Vec<_>>())
-        .flatten()
-        .collect();
+    let tvshows = tvshow_lists
+        .read()
+        .unwrap()
+        .get(&kodi.name)
+        .cloned()
+        .unwrap_or_default();
+    let missing = hospital::find_missing_episodes(&tvshows);
 
-    event!(Level::INFO, "found duplicates: {}", dups.len());
+    event!(
+        Level::INFO,
+        "found seasons with missing episodes: {}",
+        missing.len()
+    );
+    HttpResponse::Ok().json(missing)
+}
+
+#[instrument(skip(tvshow_lists), level = "info")]
+pub fn get_duplicate_episodes_list(
+    tvshow_lists: web::Data<std::sync::RwLock<TvShowLists>>,
+    kodi: Kodi,
+) -> HttpResponse {
+    let tvshows = tvshow_lists
+        .read()
+        .unwrap()
+        .get(&kodi.name)
+        .cloned()
+        .unwrap_or_default();
+    let dups = hospital::find_duplicate_episodes(&tvshows);
+
+    event!(Level::INFO, "found duplicate episodes: {}", dups.len());
     HttpResponse::Ok().json(dups)
 }
 
-#[instrument(skip(movie_list), level = "info")]
-pub fn get_recognition_errors_list(
-    movie_list: web::Data<std::sync::RwLock<Vec<Movie>>>,
+#[instrument(skip(tvshow_lists, kodi_clients), level = "info")]
+pub async fn get_unrecognized_episodes(
+    tvshow_lists: web::Data<std::sync::RwLock<TvShowLists>>,
+    kodi_clients: web::Data<std::sync::RwLock<KodiClients>>,
+    kodi: Kodi,
+) -> Result<HttpResponse, KodiRpcError> {
+    let config = CONFIG.read().unwrap().clone();
+    let kodi_rpc = kodi_clients.read().unwrap().rpc(&kodi)?;
+    let files = hospital::list_episode_files(&kodi_rpc, &config).await?;
+    let tvshows = tvshow_lists
+        .read()
+        .unwrap()
+        .get(&kodi.name)
+        .cloned()
+        .unwrap_or_default();
+
+    let missing = hospital::find_unknown_episode_files(files, &tvshows, &config);
+
+    event!(Level::INFO, "found missing episodes: {}", missing.len());
+
+    Ok(HttpResponse::Ok().json(missing))
+}
+
+#[instrument(skip(music_libraries), level = "info")]
+pub fn get_albums_without_art_list(
+    music_libraries: web::Data<std::sync::RwLock<MusicLibraries>>,
+    kodi: Kodi,
 ) -> HttpResponse {
-    let readable_movie_list = movie_list.read().unwrap().clone();
-    let config = CONFIG.read().unwrap();
-    let movie_pattern = regex::Regex::new(&format!(
-        "^{}{}",
-        config.movies_directory, config.movie_pattern
-    ))
-    .unwrap();
-
-    let diffs: Vec<Movie> = readable_movie_list
-        .into_iter()
-        .map(|movie| {
-            (
-                movie.clone(),
-                movie_pattern
-                    .captures(&movie.path)
-                    .map(|c| {
-                        (
-                            c.name("title")
-                                .map(|mt| mt.as_str().to_string())
-                                .unwrap_or_else(|| String::from("")),
-                            c.name("year")
-                                .map(|my| my.as_str().to_string())
-                                .unwrap_or_else(|| String::from("")),
-                        )
-                    })
-                    .unwrap_or_else(|| (String::from(""), String::from(""))),
-            )
-        })
-        .filter(|(movie, (title, year))| {
-            strsim::levenshtein(&movie.title, title)
-                > config.name_differences_threshold.unwrap_or(3)
-                || !movie.premiered.starts_with(year)
-        })
-        .map(|(movie, _)| movie)
-        .collect();
+    let albums = music_libraries
+        .read()
+        .unwrap()
+        .get(&kodi.name)
+        .map(hospital::find_albums_without_art)
+        .unwrap_or_default();
 
-    event!(Level::INFO, "found recognition errors: {}", diffs.len());
-    HttpResponse::Ok().json(diffs)
+    event!(Level::INFO, "found albums without art: {}", albums.len());
+    HttpResponse::Ok().json(albums)
+}
+
+#[instrument(skip(music_libraries), level = "info")]
+pub fn get_songs_without_album_list(
+    music_libraries: web::Data<std::sync::RwLock<MusicLibraries>>,
+    kodi: Kodi,
+) -> HttpResponse {
+    let songs = music_libraries
+        .read()
+        .unwrap()
+        .get(&kodi.name)
+        .map(hospital::find_songs_without_album)
+        .unwrap_or_default();
+
+    event!(Level::INFO, "found songs without album: {}", songs.len());
+    HttpResponse::Ok().json(songs)
+}
+
+#[instrument(skip(music_libraries), level = "info")]
+pub fn get_duplicate_albums_list(
+    music_libraries: web::Data<std::sync::RwLock<MusicLibraries>>,
+    kodi: Kodi,
+) -> HttpResponse {
+    let dups = music_libraries
+        .read()
+        .unwrap()
+        .get(&kodi.name)
+        .map(hospital::find_duplicate_albums)
+        .unwrap_or_default();
+
+    event!(Level::INFO, "found duplicate albums: {}", dups.len());
+    HttpResponse::Ok().json(dups)
 }
@@ -0,0 +1,36 @@
+use actix_web::{web, HttpResponse};
+use futures::StreamExt;
+use tracing::instrument;
+
+use crate::events::EVENTS;
+
+/// Interval between keep-alive comments, so that proxies don't close idle connections
+const KEEP_ALIVE_INTERVAL: std::time::Duration = std::time::Duration::from_secs(30);
+
+#[instrument(level = "info")]
+pub fn get_event_stream() -> HttpResponse {
+    let events = EVENTS.subscribe().map(|event| {
+        format!(
+            "event: {}\ndata: {}\n\n",
+            event.name(),
+            serde_json::to_string(&event).unwrap_or_default()
+        )
+    });
+    // the response ends with the events, when the subscriber fell behind, for the browser to
+    // connect again instead of waiting for events that will never come
+    let events = events
+        .map(Some)
+        .chain(futures::stream::once(futures::future::ready(None)));
+    let keep_alive =
+        actix_rt::time::interval(KEEP_ALIVE_INTERVAL).map(|_| Some(":\n\n".to_string()));
+
+    HttpResponse::Ok()
+        .content_type("text/event-stream")
+        .header("Cache-Control", "no-cache")
+        .streaming(
+            futures::stream::select(events, keep_alive)
+                .take_while(|message| futures::future::ready(message.is_some()))
+                .filter_map(futures::future::ready)
+                .map(|message| Ok::<_, actix_web::Error>(web::Bytes::from(message))),
+        )
+}
@@ -0,0 +1,26 @@
+use actix_web::{web, HttpResponse};
+use tracing::instrument;
+
+use crate::cache::History;
+use crate::Kodi;
+
+#[instrument(skip(history), level = "info")]
+pub fn get_history(history: web::Data<std::sync::RwLock<History>>) -> HttpResponse {
+    HttpResponse::Ok().json(history.read().unwrap().clone())
+}
+
+#[instrument(skip(history), level = "info")]
+pub fn get_kodi_history(
+    history: web::Data<std::sync::RwLock<History>>,
+    kodi: Kodi,
+) -> HttpResponse {
+    HttpResponse::Ok().json(
+        history
+            .read()
+            .unwrap()
+            .iter()
+            .filter(|entry| entry.kodi == kodi.name)
+            .cloned()
+            .collect::<History>(),
+    )
+}
@@ -0,0 +1,164 @@
+use actix_web::{web, HttpResponse};
+use serde::Deserialize;
+use tracing::instrument;
+
+use crate::cache::History;
+use crate::events::{Event, EVENTS};
+use crate::jobs::{Job, JobHandle, Operation, JOBS};
+use crate::kodi_rpc::{self, KodiClients};
+use crate::{Kodi, MovieLists};
+
+/// Path parameters of a job route
+#[derive(Deserialize, Debug)]
+pub struct JobPath {
+    pub job_id: u64,
+}
+
+async fn clean(
+    kodi_rpc: &kodi_rpc::KodiRPC,
+    kodi: &Kodi,
+    handle: JobHandle,
+) -> Result<(), kodi_rpc::KodiRpcError> {
+    handle.log("cleaning library");
+    EVENTS.send(Event::CleanStarted {
+        kodi: kodi.name.clone(),
+    });
+    let cleaned = kodi_rpc.clean_video_library().await;
+    EVENTS.send(Event::CleanFinished {
+        kodi: kodi.name.clone(),
+        success: cleaned.is_ok(),
+    });
+    cleaned?;
+    handle.log("library cleaned");
+    Ok(())
+}
+
+async fn scan(
+    kodi_rpc: &kodi_rpc::KodiRPC,
+    kodi: &Kodi,
+    handle: JobHandle,
+) -> Result<(), kodi_rpc::KodiRpcError> {
+    handle.log("scanning library");
+    EVENTS.send(Event::ScanStarted {
+        kodi: kodi.name.clone(),
+    });
+    let scanned = kodi_rpc.scan_video_library_and_wait_for_done().await;
+    EVENTS.send(Event::ScanFinished {
+        kodi: kodi.name.clone(),
+        success: scanned.is_ok(),
+    });
+    scanned?;
+    handle.log("library scanned");
+    Ok(())
+}
+
+async fn refresh_movie_list(
+    movie_lists: web::Data<std::sync::RwLock<MovieLists>>,
+    history: web::Data<std::sync::RwLock<History>>,
+    kodi_clients: web::Data<std::sync::RwLock<KodiClients>>,
+    kodi: &Kodi,
+    handle: JobHandle,
+) -> Result<serde_json::Value, Box<dyn std::error::Error>> {
+    handle.log("refreshing movie list");
+    let movie_lists = crate::update_kodi_movie_list(movie_lists, history, kodi_clients, kodi).await;
+    let count = movie_lists
+        .read()
+        .unwrap()
+        .get(&kodi.name)
+        .map(|movie_list| movie_list.len())
+        .unwrap_or(0);
+    handle.log(&format!("found {} movies", count));
+    Ok(serde_json::json!({ "movies": count }))
+}
+
+#[instrument(skip(movie_lists, history, kodi_clients, handle), err, level = "info")]
+async fn run_operation(
+    movie_lists: web::Data<std::sync::RwLock<MovieLists>>,
+    history: web::Data<std::sync::RwLock<History>>,
+    kodi_clients: web::Data<std::sync::RwLock<KodiClients>>,
+    kodi: Kodi,
+    operation: Operation,
+    handle: JobHandle,
+) -> Result<serde_json::Value, Box<dyn std::error::Error>> {
+    let kodi_rpc = kodi_clients.read().unwrap().rpc(&kodi)?;
+
+    match operation {
+        Operation::Clean => {
+            clean(&kodi_rpc, &kodi, handle).await?;
+            Ok(serde_json::Value::Null)
+        }
+        Operation::Scan => {
+            scan(&kodi_rpc, &kodi, handle).await?;
+            Ok(serde_json::Value::Null)
+        }
+        Operation::CleanAndScan => {
+            clean(&kodi_rpc, &kodi, handle).await?;
+            handle.progress(0.3);
+            scan(&kodi_rpc, &kodi, handle).await?;
+            handle.progress(0.9);
+            refresh_movie_list(movie_lists, history, kodi_clients, &kodi, handle).await
+        }
+        Operation::RefreshMovieList => {
+            refresh_movie_list(movie_lists, history, kodi_clients, &kodi, handle).await
+        }
+        Operation::RefreshMovies { movie_ids } => {
+            for (i, movie_id) in movie_ids.iter().enumerate() {
+                handle.log(&format!("refreshing movie {}", movie_id));
+                kodi_rpc.refresh_movie(*movie_id).await?;
+                handle.progress((i + 1) as f32 / movie_ids.len() as f32);
+            }
+            Ok(serde_json::json!({ "refreshed": movie_ids.len() }))
+        }
+    }
+}
+
+/// Start an operation as a background job
+pub fn spawn_operation(
+    movie_lists: web::Data<std::sync::RwLock<MovieLists>>,
+    history: web::Data<std::sync::RwLock<History>>,
+    kodi_clients: web::Data<std::sync::RwLock<KodiClients>>,
+    kodi: Kodi,
+    operation: Operation,
+) -> Job {
+    JOBS.spawn(&kodi.name.clone(), operation.clone(), move |handle| {
+        run_operation(movie_lists, history, kodi_clients, kodi, operation, handle)
+    })
+}
+
+#[instrument(skip(movie_lists, history, kodi_clients), level = "info")]
+pub fn start_job(
+    movie_lists: web::Data<std::sync::RwLock<MovieLists>>,
+    history: web::Data<std::sync::RwLock<History>>,
+    kodi_clients: web::Data<std::sync::RwLock<KodiClients>>,
+    kodi: Kodi,
+    operation: web::Json<Operation>,
+) -> HttpResponse {
+    HttpResponse::Accepted().json(spawn_operation(
+        movie_lists,
+        history,
+        kodi_clients,
+        kodi,
+        operation.into_inner(),
+    ))
+}
+
+#[instrument(level = "info")]
+pub fn get_job_list() -> HttpResponse {
+    HttpResponse::Ok().json(JOBS.list())
+}
+
+#[instrument(level = "info")]
+pub fn get_job(path: web::Path<JobPath>) -> HttpResponse {
+    match JOBS.get(path.job_id) {
+        Some(job) => HttpResponse::Ok().json(job),
+        None => HttpResponse::NotFound().json("unknown job"),
+    }
+}
+
+#[instrument(level = "info")]
+pub fn cancel_job(path: web::Path<JobPath>) -> HttpResponse {
+    match JOBS.cancel(path.job_id) {
+        Some(job) => HttpResponse::Accepted().json(job),
+        None => HttpResponse::NotFound().json("unknown job"),
+    }
+}
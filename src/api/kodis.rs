@@ -0,0 +1,30 @@
+use actix_web::{web, HttpResponse};
+use tracing::instrument;
+
+use crate::kodi_rpc::KodiClients;
+use crate::{Kodi, MovieLists, CONFIG};
+
+#[instrument(level = "info")]
+pub fn get_kodi_list() -> HttpResponse {
+    let config = CONFIG.read().unwrap();
+    HttpResponse::Ok().json(
+        config
+            .kodis
+            .iter()
+            .map(|kodi| kodi.name.clone())
+            .collect::<Vec<_>>(),
+    )
+}
+
+#[instrument(skip(movie_lists, kodi_clients), level = "info")]
+#[allow(clippy::async_yields_async)]
+pub async fn sync_kodi_library(
+    movie_lists: web::Data<std::sync::RwLock<MovieLists>>,
+    kodi_clients: web::Data<std::sync::RwLock<KodiClients>>,
+    kodi: Kodi,
+) -> HttpResponse {
+    let kodis = CONFIG.read().unwrap().kodis.clone();
+
+    let results = crate::sync_movie_details(movie_lists, kodi_clients, &kodi, &kodis).await;
+    HttpResponse::Ok().json(results)
+}
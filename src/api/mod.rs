@@ -1,4 +1,98 @@
+use serde::Deserialize;
+
+use crate::{file_operations::FileOperationError, kodi_rpc::KodiRpcError, Kodi, CONFIG};
+
+pub mod auth;
 pub mod config;
 pub mod errors;
+pub mod events;
+pub mod history;
+pub mod jobs;
+pub mod kodis;
 pub mod movie;
 pub mod movies;
+pub mod music;
+pub mod stats;
+pub mod tvshows;
+
+/// Path parameters of a movie route, with or without a `{kodi}` segment
+#[derive(Deserialize, Debug)]
+pub struct MoviePath {
+    pub movie_id: u16,
+}
+
+/// The Kodi instance targeted by a request is taken from the `{kodi}` path segment, or is the
+/// first configured instance for routes without it
+impl actix_web::FromRequest for Kodi {
+    type Error = actix_web::Error;
+    type Future = futures::future::Ready<Result<Self, Self::Error>>;
+    type Config = ();
+
+    fn from_request(
+        req: &actix_web::HttpRequest,
+        _payload: &mut actix_web::dev::Payload,
+    ) -> Self::Future {
+        let config = CONFIG.read().unwrap();
+        let kodi = match req.match_info().get("kodi") {
+            Some(name) => config.get_kodi(name),
+            None => config.kodis.first(),
+        };
+
+        futures::future::ready(
+            kodi.cloned()
+                .ok_or_else(|| actix_web::error::ErrorNotFound("unknown kodi")),
+        )
+    }
+}
+
+/// Errors from Kodi are reported as a gateway error, with a JSON body describing what failed
+impl actix_web::ResponseError for KodiRpcError {
+    fn status_code(&self) -> actix_web::http::StatusCode {
+        use actix_web::http::StatusCode;
+        match self {
+            KodiRpcError::Timeout => StatusCode::GATEWAY_TIMEOUT,
+            // invalid params, most likely an unknown id
+            KodiRpcError::JsonRpc(err) if err.code == -32602 => StatusCode::BAD_REQUEST,
+            KodiRpcError::InvalidUrl(_) | KodiRpcError::Configuration(_) => {
+                StatusCode::INTERNAL_SERVER_ERROR
+            }
+            _ => StatusCode::BAD_GATEWAY,
+        }
+    }
+
+    fn error_response(&self) -> actix_web::HttpResponse {
+        let mut body = serde_json::json!({
+            "error": self.kind(),
+            "message": self.to_string(),
+        });
+        match self {
+            KodiRpcError::JsonRpc(err) => body["code"] = err.code.into(),
+            KodiRpcError::HttpStatus(status) => body["status"] = (*status).into(),
+            KodiRpcError::Deserialization { field, .. } => body["field"] = field.clone().into(),
+            _ => (),
+        }
+        actix_web::HttpResponse::build(self.status_code()).json(body)
+    }
+}
+
+impl actix_web::ResponseError for FileOperationError {
+    fn status_code(&self) -> actix_web::http::StatusCode {
+        use actix_web::http::StatusCode;
+        match self {
+            FileOperationError::Kodi(err) => err.status_code(),
+            FileOperationError::Io(_) => StatusCode::INTERNAL_SERVER_ERROR,
+            FileOperationError::InvalidToken => StatusCode::BAD_REQUEST,
+            _ => StatusCode::CONFLICT,
+        }
+    }
+
+    fn error_response(&self) -> actix_web::HttpResponse {
+        if let FileOperationError::Kodi(err) = self {
+            return err.error_response();
+        }
+        actix_web::HttpResponse::build(self.status_code()).json(serde_json::json!({
+            "error": self.kind(),
+            "message": self.to_string(),
+        }))
+    }
+}
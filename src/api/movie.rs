@@ -1,46 +1,89 @@
 use actix_web::{web, HttpResponse};
+use serde::Deserialize;
 use tracing::instrument;
 
-use crate::{kodi_rpc, Movie, CONFIG};
+use super::MoviePath;
+use crate::events::{Event, EVENTS};
+use crate::file_operations::{self, FileOperationError};
+use crate::kodi_rpc::{self, KodiClients, KodiRpcError};
+use crate::{Kodi, MovieLists, CONFIG};
 
-#[instrument(level = "info")]
-pub async fn refresh_movie(movie_id: web::Path<u16>) -> HttpResponse {
-    let config = CONFIG.read().unwrap();
-
-    let kodi_rpc = kodi_rpc::KodiRPC::new(&config.kodis[0].url);
-    if let Err(err) = kodi_rpc.refresh_movie(*movie_id).await {
-        HttpResponse::InternalServerError().json(format!("error: {}", err))
-    } else {
-        HttpResponse::Ok().json("ok")
-    }
+#[instrument(skip(kodi_clients), level = "info")]
+pub async fn refresh_movie(
+    kodi_clients: web::Data<std::sync::RwLock<KodiClients>>,
+    kodi: Kodi,
+    path: web::Path<MoviePath>,
+) -> Result<HttpResponse, KodiRpcError> {
+    let kodi_rpc = kodi_clients.read().unwrap().rpc(&kodi)?;
+    kodi_rpc.refresh_movie(path.movie_id).await?;
+    Ok(HttpResponse::Ok().json("ok"))
 }
 
-#[instrument(skip(movie_list), level = "info")]
+#[instrument(skip(movie_lists, kodi_clients), level = "info")]
 pub async fn set_movie_tags(
-    movie_list: web::Data<std::sync::RwLock<Vec<Movie>>>,
-    movie_id: web::Path<u16>,
+    movie_lists: web::Data<std::sync::RwLock<MovieLists>>,
+    kodi_clients: web::Data<std::sync::RwLock<KodiClients>>,
+    kodi: Kodi,
+    path: web::Path<MoviePath>,
     tags: web::Json<Vec<String>>,
-) -> HttpResponse {
-    let mut movie_list = movie_list.write().unwrap();
+) -> Result<HttpResponse, KodiRpcError> {
+    if let Some(movie_list) = movie_lists.write().unwrap().get_mut(&kodi.name) {
+        movie_list
+            .iter_mut()
+            .filter(|movie| movie.id == path.movie_id)
+            .for_each(|movie| movie.tags = tags.clone());
+    }
 
-    *movie_list = movie_list
-        .iter()
-        .map(|movie| {
-            if movie.id == *movie_id {
-                let mut movie = movie.clone();
-                movie.tags = tags.clone();
-                movie
-            } else {
-                movie.clone()
-            }
-        })
-        .collect();
+    let kodi_rpc = kodi_clients.read().unwrap().rpc(&kodi)?;
+    kodi_rpc
+        .set_movie_details(
+            path.movie_id,
+            kodi_rpc::MovieDetails {
+                tag: Some((*tags).clone()),
+                ..Default::default()
+            },
+        )
+        .await?;
+    EVENTS.send(Event::TagsUpdated {
+        kodi: kodi.name.clone(),
+        movie_id: path.movie_id,
+        tags: (*tags).clone(),
+    });
+    Ok(HttpResponse::Ok().json("ok"))
+}
+
+#[derive(Deserialize, Debug)]
+pub struct FileActionRequest {
+    action: file_operations::Action,
+    /// Token of the preview, the action is only done with it
+    token: Option<String>,
+}
 
-    let config = CONFIG.read().unwrap();
-    let kodi_rpc = kodi_rpc::KodiRPC::new(&config.kodis[0].url);
-    if let Err(err) = kodi_rpc.set_movie_details(*movie_id, (*tags).clone()).await {
-        HttpResponse::InternalServerError().json(format!("error: {}", err))
-    } else {
-        HttpResponse::Ok().json("ok")
+/// Preview an action on the file of a movie, or do it when the token of the preview is given. Files
+/// are only changed through `path_mappings`, a movie outside of them is refused as not mapped.
+#[instrument(skip(kodi_clients), level = "info")]
+pub async fn file_action(
+    kodi_clients: web::Data<std::sync::RwLock<KodiClients>>,
+    kodi: Kodi,
+    path: web::Path<MoviePath>,
+    request: web::Json<FileActionRequest>,
+) -> Result<HttpResponse, FileOperationError> {
+    let config = CONFIG.read().unwrap().clone();
+    let kodi_rpc = kodi_clients.read().unwrap().rpc(&kodi)?;
+    match request.token.as_ref() {
+        None => Ok(HttpResponse::Ok().json(
+            file_operations::prepare(
+                &kodi_rpc,
+                &kodi.name,
+                path.movie_id,
+                request.action,
+                &config,
+            )
+            .await?,
+        )),
+        Some(token) => Ok(HttpResponse::Ok().json(
+            file_operations::execute(&kodi_rpc, &kodi.name, path.movie_id, request.action, token)
+                .await?,
+        )),
     }
 }
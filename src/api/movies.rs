@@ -1,36 +1,65 @@
 use actix_web::{web, HttpResponse};
-use tracing::{event, instrument, Level};
+use tracing::instrument;
 
-use crate::{kodi_rpc, Movie, CONFIG};
+use crate::jobs::Operation;
+use crate::kodi_rpc::KodiClients;
+use crate::{cache::History, Kodi, MovieFilter, MovieLists, CONFIG};
 
-#[instrument(skip(movie_list), level = "info")]
+#[instrument(skip(movie_lists, history, kodi_clients), level = "info")]
+#[allow(clippy::async_yields_async)]
 pub async fn update_movie_list(
-    movie_list: web::Data<std::sync::RwLock<Vec<Movie>>>,
+    movie_lists: web::Data<std::sync::RwLock<MovieLists>>,
+    history: web::Data<std::sync::RwLock<History>>,
+    kodi_clients: web::Data<std::sync::RwLock<KodiClients>>,
+    kodi: Kodi,
 ) -> HttpResponse {
-    let movie_list = crate::update_movie_list(movie_list).await;
+    let movie_lists =
+        crate::update_kodi_movie_list(movie_lists, history, kodi_clients, &kodi).await;
 
-    get_movie_list(movie_list)
+    get_movie_list(movie_lists, kodi, web::Query(MovieFilter::default()))
 }
 
-#[instrument(skip(movie_list), level = "info")]
-pub fn get_movie_list(movie_list: web::Data<std::sync::RwLock<Vec<Movie>>>) -> HttpResponse {
-    let readable_movie_list = movie_list.read().unwrap();
-    HttpResponse::Ok().json(readable_movie_list.clone())
+#[instrument(skip(movie_lists), level = "info")]
+pub fn get_movie_list(
+    movie_lists: web::Data<std::sync::RwLock<MovieLists>>,
+    kodi: Kodi,
+    filter: web::Query<MovieFilter>,
+) -> HttpResponse {
+    let readable_movie_lists = movie_lists.read().unwrap();
+    HttpResponse::Ok().json(
+        readable_movie_lists
+            .get(&kodi.name)
+            .into_iter()
+            .flatten()
+            .filter(|movie| filter.matches(movie))
+            .collect::<Vec<_>>(),
+    )
 }
 
-#[instrument(skip(movie_list), level = "info")]
-pub async fn clean_and_scan_kodi_library(
-    movie_list: web::Data<std::sync::RwLock<Vec<Movie>>>,
+#[instrument(skip(movie_lists), level = "info")]
+pub fn get_merged_movie_list(
+    movie_lists: web::Data<std::sync::RwLock<MovieLists>>,
 ) -> HttpResponse {
-    event!(Level::INFO, "starting");
     let config = CONFIG.read().unwrap();
+    let readable_movie_lists = movie_lists.read().unwrap();
+    HttpResponse::Ok().json(crate::merge_movie_lists(
+        &readable_movie_lists,
+        &config.kodis,
+    ))
+}
 
-    let kodi_rpc = kodi_rpc::KodiRPC::new(&config.kodis[0].url);
-    if let Err(_) = kodi_rpc.clean_video_library().await {
-        HttpResponse::InternalServerError().json("err")
-    } else if let Err(_) = kodi_rpc.scan_video_library_and_wait_for_done().await {
-        HttpResponse::InternalServerError().json("err")
-    } else {
-        update_movie_list(movie_list).await
-    }
+#[instrument(skip(movie_lists, history, kodi_clients), level = "info")]
+pub fn clean_and_scan_kodi_library(
+    movie_lists: web::Data<std::sync::RwLock<MovieLists>>,
+    history: web::Data<std::sync::RwLock<History>>,
+    kodi_clients: web::Data<std::sync::RwLock<KodiClients>>,
+    kodi: Kodi,
+) -> HttpResponse {
+    HttpResponse::Accepted().json(super::jobs::spawn_operation(
+        movie_lists,
+        history,
+        kodi_clients,
+        kodi,
+        Operation::CleanAndScan,
+    ))
 }
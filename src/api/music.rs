@@ -0,0 +1,62 @@
+use actix_web::{web, HttpResponse};
+use tracing::instrument;
+
+use crate::kodi_rpc::KodiClients;
+use crate::{Kodi, MusicLibraries, MusicLibrary};
+
+#[instrument(skip(music_libraries, kodi_clients), level = "info")]
+#[allow(clippy::async_yields_async)]
+pub async fn update_music_library(
+    music_libraries: web::Data<std::sync::RwLock<MusicLibraries>>,
+    kodi_clients: web::Data<std::sync::RwLock<KodiClients>>,
+    kodi: Kodi,
+) -> HttpResponse {
+    let music_libraries =
+        crate::update_kodi_music_library(music_libraries, kodi_clients, &kodi).await;
+
+    get_music_library(music_libraries, kodi)
+}
+
+fn read_music_library(
+    music_libraries: &web::Data<std::sync::RwLock<MusicLibraries>>,
+    kodi: &Kodi,
+) -> MusicLibrary {
+    music_libraries
+        .read()
+        .unwrap()
+        .get(&kodi.name)
+        .cloned()
+        .unwrap_or_default()
+}
+
+#[instrument(skip(music_libraries), level = "info")]
+pub fn get_music_library(
+    music_libraries: web::Data<std::sync::RwLock<MusicLibraries>>,
+    kodi: Kodi,
+) -> HttpResponse {
+    HttpResponse::Ok().json(read_music_library(&music_libraries, &kodi))
+}
+
+#[instrument(skip(music_libraries), level = "info")]
+pub fn get_artist_list(
+    music_libraries: web::Data<std::sync::RwLock<MusicLibraries>>,
+    kodi: Kodi,
+) -> HttpResponse {
+    HttpResponse::Ok().json(read_music_library(&music_libraries, &kodi).artists)
+}
+
+#[instrument(skip(music_libraries), level = "info")]
+pub fn get_album_list(
+    music_libraries: web::Data<std::sync::RwLock<MusicLibraries>>,
+    kodi: Kodi,
+) -> HttpResponse {
+    HttpResponse::Ok().json(read_music_library(&music_libraries, &kodi).albums)
+}
+
+#[instrument(skip(music_libraries), level = "info")]
+pub fn get_song_list(
+    music_libraries: web::Data<std::sync::RwLock<MusicLibraries>>,
+    kodi: Kodi,
+) -> HttpResponse {
+    HttpResponse::Ok().json(read_music_library(&music_libraries, &kodi).songs)
+}
@@ -0,0 +1,18 @@
+use actix_web::{web, HttpResponse};
+use tracing::instrument;
+
+use crate::{stats, Kodi, MovieLists};
+
+#[instrument(skip(movie_lists), level = "info")]
+pub fn get_quality_stats(
+    movie_lists: web::Data<std::sync::RwLock<MovieLists>>,
+    kodi: Kodi,
+) -> HttpResponse {
+    let readable_movie_lists = movie_lists.read().unwrap();
+    HttpResponse::Ok().json(stats::quality_stats(
+        readable_movie_lists
+            .get(&kodi.name)
+            .map(Vec::as_slice)
+            .unwrap_or_default(),
+    ))
+}
@@ -0,0 +1,56 @@
+use actix_web::{web, HttpResponse};
+use serde::Deserialize;
+use tracing::instrument;
+
+use crate::kodi_rpc::KodiClients;
+use crate::{Kodi, TvShowLists};
+
+/// Path parameters of a TV show route, with or without a `{kodi}` segment
+#[derive(Deserialize, Debug)]
+pub struct TvShowPath {
+    pub tvshow_id: u16,
+}
+
+#[instrument(skip(tvshow_lists, kodi_clients), level = "info")]
+#[allow(clippy::async_yields_async)]
+pub async fn update_tvshow_list(
+    tvshow_lists: web::Data<std::sync::RwLock<TvShowLists>>,
+    kodi_clients: web::Data<std::sync::RwLock<KodiClients>>,
+    kodi: Kodi,
+) -> HttpResponse {
+    let tvshow_lists = crate::update_kodi_tvshow_list(tvshow_lists, kodi_clients, &kodi).await;
+
+    get_tvshow_list(tvshow_lists, kodi)
+}
+
+#[instrument(skip(tvshow_lists), level = "info")]
+pub fn get_tvshow_list(
+    tvshow_lists: web::Data<std::sync::RwLock<TvShowLists>>,
+    kodi: Kodi,
+) -> HttpResponse {
+    let readable_tvshow_lists = tvshow_lists.read().unwrap();
+    HttpResponse::Ok().json(
+        readable_tvshow_lists
+            .get(&kodi.name)
+            .cloned()
+            .unwrap_or_default(),
+    )
+}
+
+#[instrument(skip(tvshow_lists), level = "info")]
+pub fn get_tvshow(
+    tvshow_lists: web::Data<std::sync::RwLock<TvShowLists>>,
+    kodi: Kodi,
+    path: web::Path<TvShowPath>,
+) -> HttpResponse {
+    let readable_tvshow_lists = tvshow_lists.read().unwrap();
+    match readable_tvshow_lists
+        .get(&kodi.name)
+        .into_iter()
+        .flatten()
+        .find(|tvshow| tvshow.id == path.tvshow_id)
+    {
+        Some(tvshow) => HttpResponse::Ok().json(tvshow),
+        None => HttpResponse::NotFound().json("unknown TV show"),
+    }
+}
@@ -0,0 +1,197 @@
+use lazy_static::lazy_static;
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+use sha2::Digest;
+
+lazy_static! {
+    pub static ref SESSIONS: Sessions = Sessions::default();
+}
+
+/// What a user or token is allowed to do. A viewer can browse the library, an admin can also
+/// tag, refresh, clean and scan.
+#[derive(Deserialize, Serialize, Clone, Copy, Debug, PartialEq, PartialOrd)]
+#[serde(rename_all = "snake_case")]
+pub enum Role {
+    Viewer,
+    Admin,
+}
+
+#[derive(Deserialize, Serialize, Clone, Debug)]
+pub struct User {
+    pub username: String,
+    /// bcrypt hash of the password
+    pub password_hash: String,
+    pub role: Role,
+}
+
+#[derive(Deserialize, Serialize, Clone, Debug)]
+pub struct ApiToken {
+    pub name: String,
+    /// Hex encoded SHA-256 of the token
+    pub token_sha256: String,
+    pub role: Role,
+}
+
+/// Access control of the web server
+#[derive(Deserialize, Serialize, Clone, Debug)]
+pub struct AuthConfig {
+    #[serde(default)]
+    pub users: Vec<User>,
+    #[serde(default)]
+    pub tokens: Vec<ApiToken>,
+    /// Time before a session expires, in seconds
+    #[serde(default = "get_default_session_duration")]
+    pub session_duration: u64,
+}
+
+fn get_default_session_duration() -> u64 {
+    60 * 60 * 24 * 7
+}
+
+/// Who is making a request
+#[derive(Serialize, Clone, Debug)]
+pub struct Identity {
+    pub name: String,
+    pub role: Role,
+}
+
+impl AuthConfig {
+    /// Check the credentials of a user. This is slow on purpose, don't call it on an executor
+    /// thread.
+    pub fn check_password(&self, username: &str, password: &str) -> Option<Identity> {
+        let user = self.users.iter().find(|user| user.username == username)?;
+        if bcrypt::verify(password, &user.password_hash).unwrap_or(false) {
+            Some(Identity {
+                name: user.username.clone(),
+                role: user.role,
+            })
+        } else {
+            None
+        }
+    }
+
+    pub fn check_token(&self, token: &str) -> Option<Identity> {
+        let token_sha256 = format!("{:x}", sha2::Sha256::digest(token.as_bytes()));
+        self.tokens
+            .iter()
+            .find(|known| known.token_sha256.eq_ignore_ascii_case(&token_sha256))
+            .map(|known| Identity {
+                name: known.name.clone(),
+                role: known.role,
+            })
+    }
+}
+
+struct Session {
+    identity: Identity,
+    expires: std::time::Instant,
+}
+
+/// Sessions of logged in users, forgotten on restart
+#[derive(Default)]
+pub struct Sessions {
+    sessions: std::sync::Mutex<std::collections::HashMap<String, Session>>,
+}
+
+impl Sessions {
+    /// Start a session, returning its id
+    pub fn create(&self, identity: Identity, duration: std::time::Duration) -> String {
+        let id = base64::encode_config(
+            rand::thread_rng().gen::<[u8; 32]>(),
+            base64::URL_SAFE_NO_PAD,
+        );
+        let mut sessions = self.sessions.lock().unwrap();
+        let now = std::time::Instant::now();
+        sessions.retain(|_, session| session.expires > now);
+        sessions.insert(
+            id.clone(),
+            Session {
+                identity,
+                expires: now + duration,
+            },
+        );
+        id
+    }
+
+    pub fn get(&self, id: &str) -> Option<Identity> {
+        self.sessions
+            .lock()
+            .unwrap()
+            .get(id)
+            .filter(|session| session.expires > std::time::Instant::now())
+            .map(|session| session.identity.clone())
+    }
+
+    pub fn remove(&self, id: &str) {
+        self.sessions.lock().unwrap().remove(id);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn auth() -> AuthConfig {
+        AuthConfig {
+            users: vec![User {
+                username: "admin".to_string(),
+                password_hash: bcrypt::hash("password", 4).unwrap(),
+                role: Role::Admin,
+            }],
+            tokens: vec![ApiToken {
+                name: "script".to_string(),
+                // SHA-256 of "secret", as written by `sha256sum`
+                token_sha256: "2BB80D537B1DA3E38BD30361AA855686BDE0EACD7162FEF6A25FE97BF527A25B"
+                    .to_string(),
+                role: Role::Viewer,
+            }],
+            session_duration: 60,
+        }
+    }
+
+    #[test]
+    fn check_credentials() {
+        let auth = auth();
+        let name = |identity: Option<Identity>| identity.map(|identity| identity.name);
+
+        assert_eq!(name(auth.check_token("secret")), Some("script".to_string()));
+        assert_eq!(name(auth.check_token("Secret")), None);
+        assert_eq!(name(auth.check_token("")), None);
+        // the hash itself is not a token
+        assert_eq!(name(auth.check_token(&auth.tokens[0].token_sha256)), None);
+
+        assert_eq!(
+            name(auth.check_password("admin", "password")),
+            Some("admin".to_string())
+        );
+        assert_eq!(name(auth.check_password("admin", "wrong")), None);
+        assert_eq!(name(auth.check_password("admin", "")), None);
+        assert_eq!(name(auth.check_password("nobody", "password")), None);
+    }
+
+    #[test]
+    fn sessions_expire() {
+        let sessions = Sessions::default();
+        let identity = Identity {
+            name: "admin".to_string(),
+            role: Role::Admin,
+        };
+        let session = sessions.create(identity.clone(), std::time::Duration::from_secs(60));
+        let expired = sessions.create(identity.clone(), std::time::Duration::from_secs(0));
+        assert_ne!(session, expired);
+
+        assert_eq!(
+            sessions.get(&session).map(|identity| identity.role),
+            Some(Role::Admin)
+        );
+        assert!(sessions.get(&expired).is_none());
+        assert!(sessions.get("unknown").is_none());
+
+        // expired sessions are forgotten when a new one starts
+        sessions.create(identity, std::time::Duration::from_secs(60));
+        assert!(!sessions.sessions.lock().unwrap().contains_key(&expired));
+
+        sessions.remove(&session);
+        assert!(sessions.get(&session).is_none());
+    }
+}
@@ -0,0 +1,456 @@
+use clap::Clap;
+
+use kodi_helper::file_operations::{self, Action};
+use kodi_helper::kodi_rpc::{KodiRPC, MovieDetails};
+use kodi_helper::{hospital, Config, Kodi, Movie, MovieFilter, Resolution};
+
+#[derive(Clap)]
+#[clap(version = "1.0", author = "François")]
+/// Maintain a Kodi library from the command line
+struct CliOpts {
+    /// conf path
+    #[clap(short = 'c', long = "config", default_value = "config.conf")]
+    config: String,
+    /// Kodi instance to use, the first configured one by default
+    #[clap(short = 'k', long = "kodi")]
+    kodi: Option<String>,
+    #[clap(subcommand)]
+    command: Command,
+}
+
+#[derive(Clap)]
+enum Command {
+    /// list the movies of the library
+    Movies(MoviesCommand),
+    /// scan the video library
+    Scan {
+        /// wait for the scan to be done
+        #[clap(long = "wait")]
+        wait: bool,
+    },
+    /// clean the video library, waiting for it to be done
+    Clean,
+    /// refresh movies from their online sources
+    Refresh {
+        /// ids of the movies to refresh
+        movie_ids: Vec<u16>,
+        /// refresh every movie badly recognized or without a poster
+        #[clap(long = "all-errors")]
+        all_errors: bool,
+    },
+    /// look for issues in the library
+    Hospital(HospitalCommand),
+    /// add or remove tags of a movie
+    Tag(TagCommand),
+    /// rename, move or delete the file of a movie, or remove a movie whose file is gone from the
+    /// library, showing what would be done without --yes
+    File {
+        #[clap(possible_values = &["rename", "move", "delete", "remove"])]
+        action: Action,
+        movie_id: u16,
+        /// do it
+        #[clap(long = "yes")]
+        yes: bool,
+    },
+}
+
+#[derive(Clap)]
+enum MoviesCommand {
+    /// list the movies of the library
+    List {
+        #[clap(short = 'f', long = "format", default_value = "table", possible_values = &["table", "json", "csv"])]
+        format: Format,
+        #[clap(flatten)]
+        filter: FilterOpts,
+    },
+}
+
+/// Filters on the quality of movies, text is compared ignoring case
+#[derive(Clap)]
+struct FilterOpts {
+    #[clap(long = "resolution", possible_values = &["Sd", "Hd720p", "Hd1080p", "Uhd4k", "Uhd8k"])]
+    resolution: Option<Resolution>,
+    #[clap(long = "video-codec")]
+    video_codec: Option<String>,
+    /// HDR type, `none` for movies without HDR
+    #[clap(long = "hdr")]
+    hdr: Option<String>,
+    #[clap(long = "stereomode")]
+    stereomode: Option<String>,
+    #[clap(long = "min-aspect")]
+    min_aspect: Option<f32>,
+    #[clap(long = "max-aspect")]
+    max_aspect: Option<f32>,
+    /// minimum number of channels of the best audio stream
+    #[clap(long = "min-channels")]
+    min_channels: Option<u8>,
+    #[clap(long = "audio-codec")]
+    audio_codec: Option<String>,
+    #[clap(long = "audio-language")]
+    audio_language: Option<String>,
+    #[clap(long = "subtitle-language")]
+    subtitle_language: Option<String>,
+}
+
+impl From<FilterOpts> for MovieFilter {
+    fn from(opts: FilterOpts) -> Self {
+        MovieFilter {
+            resolution: opts.resolution,
+            video_codec: opts.video_codec,
+            hdr: opts.hdr,
+            stereomode: opts.stereomode,
+            min_aspect: opts.min_aspect,
+            max_aspect: opts.max_aspect,
+            min_channels: opts.min_channels,
+            audio_codec: opts.audio_codec,
+            audio_language: opts.audio_language,
+            subtitle_language: opts.subtitle_language,
+        }
+    }
+}
+
+#[derive(Clap)]
+enum HospitalCommand {
+    /// movies with the same title
+    Duplicates {
+        #[clap(short = 'f', long = "format", default_value = "table", possible_values = &["table", "json", "csv"])]
+        format: Format,
+    },
+    /// movies with a title too different from their file name
+    Recognition {
+        #[clap(short = 'f', long = "format", default_value = "table", possible_values = &["table", "json", "csv"])]
+        format: Format,
+    },
+    /// copies of duplicated movies ranked by quality, with the one to keep
+    Upgrades {
+        #[clap(short = 'f', long = "format", default_value = "table", possible_values = &["table", "json", "csv"])]
+        format: Format,
+    },
+    /// files of the movie sources missing from the library
+    Missing {
+        #[clap(short = 'f', long = "format", default_value = "table", possible_values = &["table", "json", "csv"])]
+        format: Format,
+    },
+    /// movies of the library whose files no longer exist
+    Orphans {
+        #[clap(short = 'f', long = "format", default_value = "table", possible_values = &["table", "json", "csv"])]
+        format: Format,
+    },
+}
+
+#[derive(Clap)]
+enum TagCommand {
+    /// add a tag to a movie
+    Add { movie_id: u16, tag: String },
+    /// remove a tag from a movie
+    Remove { movie_id: u16, tag: String },
+}
+
+#[derive(Clone, Copy)]
+enum Format {
+    Table,
+    Json,
+    Csv,
+}
+
+impl std::str::FromStr for Format {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "table" => Ok(Format::Table),
+            "json" => Ok(Format::Json),
+            "csv" => Ok(Format::Csv),
+            _ => Err(format!("unknown format {}", s)),
+        }
+    }
+}
+
+#[actix_rt::main]
+async fn main() {
+    let cli_opts: CliOpts = CliOpts::parse();
+
+    let config = match kodi_helper::config::load(&cli_opts.config) {
+        Ok(config) => config,
+        Err(problems) => {
+            for problem in problems {
+                eprintln!("{}", problem);
+            }
+            std::process::exit(1);
+        }
+    };
+    let kodi = match cli_opts.kodi.as_ref() {
+        Some(name) => config.get_kodi(name),
+        None => config.kodis.first(),
+    };
+    let kodi = if let Some(kodi) = kodi.cloned() {
+        kodi
+    } else {
+        eprintln!("unknown kodi {}", cli_opts.kodi.unwrap_or_default());
+        std::process::exit(1);
+    };
+
+    if let Err(err) = run(cli_opts.command, &kodi, &config).await {
+        eprintln!("{}", err);
+        std::process::exit(1);
+    }
+}
+
+async fn run(
+    command: Command,
+    kodi: &Kodi,
+    config: &Config,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let kodi_rpc = KodiRPC::new(kodi)?;
+
+    match command {
+        Command::Movies(MoviesCommand::List { format, filter }) => {
+            let filter = MovieFilter::from(filter);
+            let movies = get_movies(&kodi_rpc)
+                .await?
+                .into_iter()
+                .filter(|movie| filter.matches(movie))
+                .collect::<Vec<_>>();
+            print_movies(&movies, format);
+        }
+        Command::Scan { wait } => {
+            if wait {
+                kodi_rpc.scan_video_library_and_wait_for_done().await?;
+                println!("library scanned");
+            } else {
+                kodi_rpc.scan_video_library().await?;
+                println!("scan started");
+            }
+        }
+        Command::Clean => {
+            kodi_rpc.clean_video_library().await?;
+            println!("library cleaned");
+        }
+        Command::Refresh {
+            mut movie_ids,
+            all_errors,
+        } => {
+            if all_errors {
+                let movies = get_movies(&kodi_rpc).await?;
+                movie_ids.extend(
+                    hospital::find_recognition_errors(
+                        &movies,
+                        &hospital::source_parsers(config)?,
+                        config,
+                    )
+                    .iter()
+                    .chain(movies.iter().filter(|movie| movie.poster.is_none()))
+                    .map(|movie| movie.id),
+                );
+                movie_ids.sort_unstable();
+                movie_ids.dedup();
+            }
+            for movie_id in movie_ids {
+                kodi_rpc.refresh_movie(movie_id).await?;
+                println!("refreshed movie {}", movie_id);
+            }
+        }
+        Command::Hospital(HospitalCommand::Duplicates { format }) => {
+            let movies = get_movies(&kodi_rpc).await?;
+            let parser = hospital::source_parsers(config)?;
+            print_movies(&hospital::find_duplicates(&movies, &parser), format);
+        }
+        Command::Hospital(HospitalCommand::Recognition { format }) => {
+            let movies = get_movies(&kodi_rpc).await?;
+            let parser = hospital::source_parsers(config)?;
+            print_movies(
+                &hospital::find_recognition_errors(&movies, &parser, config),
+                format,
+            );
+        }
+        Command::Hospital(HospitalCommand::Upgrades { format }) => {
+            let movies = get_movies(&kodi_rpc).await?;
+            let candidates = hospital::rank_duplicates(&kodi_rpc, &movies, config).await?;
+            let copies = candidates.iter().flat_map(|candidate| {
+                std::iter::once((&candidate.keep, true))
+                    .chain(candidate.redundant.iter().map(|copy| (copy, false)))
+            });
+            let size = |copy: &hospital::RankedCopy| {
+                copy.size.map(|size| size.to_string()).unwrap_or_default()
+            };
+            match format {
+                Format::Json => println!("{}", serde_json::to_string_pretty(&candidates).unwrap()),
+                Format::Csv => {
+                    println!("id,title,action,reason,size,path");
+                    for (copy, keep) in copies {
+                        println!(
+                            "{},{},{},{},{},{}",
+                            copy.movie.id,
+                            csv_field(&copy.movie.title),
+                            if keep { "keep" } else { "redundant" },
+                            copy.reason.as_deref().unwrap_or_default(),
+                            size(copy),
+                            csv_field(&copy.movie.path)
+                        );
+                    }
+                }
+                Format::Table => {
+                    for (copy, keep) in copies {
+                        println!(
+                            "{:>5}  {:9}  {:20}  {:>12}  {}",
+                            copy.movie.id,
+                            if keep { "keep" } else { "redundant" },
+                            copy.reason.as_deref().unwrap_or_default(),
+                            size(copy),
+                            copy.movie.path
+                        );
+                    }
+                }
+            }
+        }
+        Command::Hospital(HospitalCommand::Missing { format }) => {
+            let movies = get_movies(&kodi_rpc).await?;
+            let missing = hospital::find_missing_movies(&kodi_rpc, &movies, config).await?;
+            match format {
+                Format::Json => println!("{}", serde_json::to_string_pretty(&missing).unwrap()),
+                Format::Csv => {
+                    println!("path");
+                    missing
+                        .iter()
+                        .for_each(|file| println!("{}", csv_field(&file.path)));
+                }
+                Format::Table => missing.iter().for_each(|file| println!("{}", file.path)),
+            }
+        }
+        Command::Hospital(HospitalCommand::Orphans { format }) => {
+            let movies = get_movies(&kodi_rpc).await?;
+            print_movies(
+                &hospital::find_orphans(&kodi_rpc, &movies, config).await?,
+                format,
+            );
+        }
+        Command::Tag(tag_command) => {
+            let (movie_id, tag, add) = match tag_command {
+                TagCommand::Add { movie_id, tag } => (movie_id, tag, true),
+                TagCommand::Remove { movie_id, tag } => (movie_id, tag, false),
+            };
+            let mut tags = kodi_rpc.get_movie_details(movie_id).await?.tags;
+            tags.retain(|known| known != &tag);
+            if add {
+                tags.push(tag);
+            }
+            kodi_rpc
+                .set_movie_details(
+                    movie_id,
+                    MovieDetails {
+                        tag: Some(tags.clone()),
+                        ..Default::default()
+                    },
+                )
+                .await?;
+            println!("{}: {}", movie_id, tags.join(", "));
+        }
+        Command::File {
+            action,
+            movie_id,
+            yes,
+        } => {
+            let plan =
+                file_operations::prepare(&kodi_rpc, &kodi.name, movie_id, action, config).await?;
+            let describe = |plan: &file_operations::Plan| {
+                let from = plan.local_from.as_ref().unwrap_or(&plan.from);
+                let sidecars = plan
+                    .sidecars
+                    .iter()
+                    .map(|sidecar| format!(", {} to {}", sidecar.local_from, sidecar.local_to))
+                    .collect::<String>();
+                match plan.local_to.as_ref() {
+                    Some(local_to) => format!("{} {} to {}{}", action, from, local_to, sidecars),
+                    None => format!("{} {}", action, from),
+                }
+            };
+            if yes {
+                let plan =
+                    file_operations::execute(&kodi_rpc, &kodi.name, movie_id, action, &plan.token)
+                        .await?;
+                println!("done: {}", describe(&plan));
+                for warning in plan.warnings.iter() {
+                    println!("warning: {}", warning);
+                }
+            } else {
+                println!("would {}, run again with --yes to do it", describe(&plan));
+            }
+        }
+    }
+
+    Ok(())
+}
+
+async fn get_movies(kodi_rpc: &KodiRPC) -> Result<Vec<Movie>, kodi_helper::kodi_rpc::KodiRpcError> {
+    Ok(kodi_helper::movie_list_cleanup(
+        kodi_rpc.get_all_movies().await?,
+    ))
+}
+
+fn csv_field(field: &str) -> String {
+    if field.contains(&[',', '"', '\n'][..]) {
+        format!("\"{}\"", field.replace('"', "\"\""))
+    } else {
+        field.to_string()
+    }
+}
+
+fn print_movies(movies: &[Movie], format: Format) {
+    let year = |movie: &Movie| movie.premiered.chars().take(4).collect::<String>();
+    let resolution = |movie: &Movie| {
+        movie
+            .resolution
+            .as_ref()
+            .map(|resolution| format!("{:?}", resolution))
+            .unwrap_or_default()
+    };
+
+    match format {
+        Format::Json => println!("{}", serde_json::to_string_pretty(movies).unwrap()),
+        Format::Csv => {
+            println!("id,title,year,resolution,rating,playcount,tags,path");
+            for movie in movies {
+                println!(
+                    "{},{},{},{},{},{},{},{}",
+                    movie.id,
+                    csv_field(&movie.title),
+                    year(movie),
+                    resolution(movie),
+                    movie.rating,
+                    movie.playcount,
+                    csv_field(&movie.tags.join(";")),
+                    csv_field(&movie.path)
+                );
+            }
+        }
+        Format::Table => {
+            let title_width = movies
+                .iter()
+                .map(|movie| movie.title.chars().count())
+                .max()
+                .unwrap_or(0)
+                .max(5);
+            println!(
+                "{:>5}  {:title_width$}  {:4}  {:10}  {:>6}  tags",
+                "id",
+                "title",
+                "year",
+                "resolution",
+                "rating",
+                title_width = title_width
+            );
+            for movie in movies {
+                println!(
+                    "{:>5}  {:title_width$}  {:4}  {:10}  {:>6.1}  {}",
+                    movie.id,
+                    movie.title,
+                    year(movie),
+                    resolution(movie),
+                    movie.rating,
+                    movie.tags.join(", "),
+                    title_width = title_width
+                );
+            }
+        }
+    }
+}
@@ -1,11 +1,11 @@
 #![type_length_limit = "2275484"]
 
 use actix_files::Files;
+use actix_web::dev::Service;
 use actix_web::{middleware, web, App, HttpServer};
 use clap::Clap;
 use tracing::instrument;
 use tracing::{event, Level};
-use tracing_subscriber;
 
 #[derive(Clap)]
 #[clap(version = "1.0", author = "François")]
@@ -13,28 +13,114 @@ struct CliOpts {
     /// conf path
     #[clap(short = 'c', long = "config", default_value = "config.conf")]
     config: String,
+    /// address to listen on, can be repeated
+    #[clap(long = "bind")]
+    bind: Vec<String>,
+    /// port to listen on
+    #[clap(short = 'p', long = "port")]
+    port: Option<u16>,
+    /// folder with the UI
+    #[clap(long = "static-dir")]
+    static_dir: Option<String>,
+    /// prefix of all routes, like `/kodi-helper/` behind a reverse proxy
+    #[clap(long = "base-url")]
+    base_url: Option<String>,
+    /// time between two refreshes of the libraries, in seconds
+    #[clap(long = "refresh-interval")]
+    refresh_interval: Option<u64>,
+    /// certificate chain to serve over https
+    #[clap(long = "tls-cert", requires = "tls-key")]
+    tls_cert: Option<String>,
+    /// private key to serve over https
+    #[clap(long = "tls-key", requires = "tls-cert")]
+    tls_key: Option<String>,
+    #[clap(subcommand)]
+    command: Option<Command>,
+}
+
+#[derive(Clap)]
+enum Command {
+    /// check the configuration, every Kodi instance and the mapped folders, then exit
+    #[clap(name = "check-config")]
+    CheckConfig,
 }
 
 #[actix_rt::main]
 async fn main() -> std::io::Result<()> {
-    let _subscriber = tracing_subscriber::fmt()
+    tracing_subscriber::fmt()
         .with_max_level(tracing::Level::INFO)
         .init();
 
-    load_config();
+    let cli_opts: CliOpts = CliOpts::parse();
+    if let Some(Command::CheckConfig) = cli_opts.command {
+        check_config(&cli_opts.config).await;
+    }
+
+    let config_file = load_config(cli_opts);
     event!(Level::INFO, "Starting");
+    let kodi_clients: web::Data<std::sync::RwLock<kodi_helper::kodi_rpc::KodiClients>> =
+        web::Data::new(std::sync::RwLock::new(
+            kodi_helper::kodi_rpc::KodiClients::from_config(&kodi_helper::CONFIG.read().unwrap()),
+        ));
+    actix_rt::spawn(kodi_helper::config::watch(
+        config_file.clone(),
+        kodi_clients.clone(),
+    ));
+    actix_rt::spawn(async move {
+        let config = kodi_helper::CONFIG.read().unwrap().clone();
+        let mut problems = kodi_helper::config::check_kodis(&config).await;
+        problems.extend(kodi_helper::config::check_path_mappings(&config));
+        if let Ok(source) = std::fs::read_to_string(&config_file) {
+            kodi_helper::config::locate(&mut problems, &source);
+        }
+        for problem in problems {
+            event!(Level::WARN, "{}", problem);
+        }
+    });
+
+    let snapshot = kodi_helper::CONFIG
+        .read()
+        .unwrap()
+        .cache_file
+        .as_ref()
+        .map(|cache_file| kodi_helper::cache::load(cache_file))
+        .unwrap_or_default();
+    let movie_lists: web::Data<std::sync::RwLock<kodi_helper::MovieLists>> =
+        web::Data::new(std::sync::RwLock::new(snapshot.movies));
+    let history: web::Data<std::sync::RwLock<kodi_helper::cache::History>> =
+        web::Data::new(std::sync::RwLock::new(snapshot.history));
+    let tvshow_lists: web::Data<std::sync::RwLock<kodi_helper::TvShowLists>> =
+        web::Data::new(std::sync::RwLock::new(kodi_helper::TvShowLists::new()));
+    let music_libraries: web::Data<std::sync::RwLock<kodi_helper::MusicLibraries>> =
+        web::Data::new(std::sync::RwLock::new(kodi_helper::MusicLibraries::new()));
 
-    let movie_list: web::Data<std::sync::RwLock<Vec<kodi_helper::Movie>>> =
-        web::Data::new(std::sync::RwLock::new(vec![]));
+    for kodi in kodi_helper::CONFIG.read().unwrap().kodis.iter() {
+        actix_rt::spawn(kodi_helper::follow_kodi_notifications(
+            movie_lists.clone(),
+            history.clone(),
+            kodi_clients.clone(),
+            kodi.clone(),
+        ));
+    }
 
-    let mut refresh_interval = actix_rt::time::interval(std::time::Duration::from_secs(60 * 30));
+    let mut refresh_interval = actix_rt::time::interval(std::time::Duration::from_secs(
+        kodi_helper::CONFIG.read().unwrap().server.refresh_interval,
+    ));
 
-    let server = setup_server(movie_list.clone())?;
+    let server = setup_server(
+        movie_lists.clone(),
+        history.clone(),
+        tvshow_lists.clone(),
+        music_libraries.clone(),
+        kodi_clients.clone(),
+    )?;
     futures::pin_mut!(server);
 
     loop {
-        let next_tick = futures::future::join(
-            refresh_movie_list(movie_list.clone()),
+        let next_tick = futures::future::join4(
+            refresh_movie_list(movie_lists.clone(), history.clone(), kodi_clients.clone()),
+            refresh_tvshow_list(tvshow_lists.clone(), kodi_clients.clone()),
+            refresh_music_library(music_libraries.clone(), kodi_clients.clone()),
             refresh_interval.tick(),
         );
         futures::pin_mut!(next_tick);
@@ -49,57 +135,416 @@ async fn main() -> std::io::Result<()> {
     Ok(())
 }
 
-fn load_config() {
-    let cli_opts: CliOpts = CliOpts::parse();
-    let loaded_config: kodi_helper::Config = hocon::HoconLoader::new()
-        .load_file(&cli_opts.config)
-        .and_then(|hc| hc.resolve())
-        .unwrap();
+/// Report every problem of the configuration, exiting with an error if there are some
+async fn check_config(path: &str) {
+    let problems = kodi_helper::config::check(path).await;
+    if problems.is_empty() {
+        println!("{} is valid", path);
+        std::process::exit(0);
+    }
+    for problem in problems {
+        eprintln!("{}", problem);
+    }
+    std::process::exit(1);
+}
+
+fn load_config(cli_opts: CliOpts) -> String {
+    let loaded_config = match kodi_helper::config::load(&cli_opts.config) {
+        Ok(config) => config,
+        Err(problems) => {
+            for problem in problems {
+                eprintln!("{}", problem);
+            }
+            std::process::exit(1);
+        }
+    };
+    *kodi_helper::config::CONFIG_FILE.write().unwrap() = Some(cli_opts.config.clone());
+    *kodi_helper::config::FILE_SERVER.write().unwrap() = Some(loaded_config.server.clone());
     let mut config = kodi_helper::CONFIG.write().unwrap();
     *config = loaded_config;
+
+    if !cli_opts.bind.is_empty() {
+        config.server.bind_addresses = cli_opts.bind;
+    }
+    if let Some(port) = cli_opts.port {
+        config.server.port = port;
+    }
+    if let Some(static_dir) = cli_opts.static_dir {
+        config.server.static_dir = static_dir;
+    }
+    if let Some(base_url) = cli_opts.base_url {
+        config.server.base_url = base_url;
+    }
+    if let Some(refresh_interval) = cli_opts.refresh_interval {
+        config.server.refresh_interval = refresh_interval;
+    }
+    if let (Some(cert), Some(key)) = (cli_opts.tls_cert, cli_opts.tls_key) {
+        config.server.tls = Some(kodi_helper::TlsConfig { cert, key });
+    }
+
+    cli_opts.config
+}
+
+fn load_tls_config(tls: &kodi_helper::TlsConfig) -> std::io::Result<rustls::ServerConfig> {
+    let invalid = |message: String| std::io::Error::new(std::io::ErrorKind::InvalidData, message);
+
+    let certs = rustls::internal::pemfile::certs(&mut std::io::BufReader::new(
+        std::fs::File::open(&tls.cert)?,
+    ))
+    .map_err(|_| invalid(format!("invalid certificate {}", tls.cert)))?;
+    let read_keys = |read: fn(
+        &mut dyn std::io::BufRead,
+    ) -> Result<Vec<rustls::PrivateKey>, ()>|
+     -> std::io::Result<Vec<rustls::PrivateKey>> {
+        read(&mut std::io::BufReader::new(std::fs::File::open(&tls.key)?))
+            .map_err(|_| invalid(format!("invalid private key {}", tls.key)))
+    };
+    let mut keys = read_keys(rustls::internal::pemfile::pkcs8_private_keys)?;
+    if keys.is_empty() {
+        keys = read_keys(rustls::internal::pemfile::rsa_private_keys)?;
+    }
+    let key = keys
+        .into_iter()
+        .next()
+        .ok_or_else(|| invalid(format!("no private key in {}", tls.key)))?;
+
+    let mut config = rustls::ServerConfig::new(rustls::NoClientAuth::new());
+    config
+        .set_single_cert(certs, key)
+        .map_err(|err| invalid(format!("invalid certificate or key: {}", err)))?;
+    Ok(config)
+}
+
+/// Serve the UI, with its base url set so that it works under a prefix
+async fn serve_index() -> actix_web::Result<actix_web::HttpResponse> {
+    let server = kodi_helper::CONFIG.read().unwrap().server.clone();
+    let index =
+        std::fs::read_to_string(std::path::Path::new(&server.static_dir).join("index.html"))?;
+    Ok(actix_web::HttpResponse::Ok()
+        .content_type("text/html; charset=utf-8")
+        .body(index.replace(
+            "<base href=\"/\">",
+            &format!("<base href=\"{}/\">", server.route_prefix()),
+        )))
+}
+
+#[instrument(skip(movie_lists, history, kodi_clients), level = "info")]
+async fn refresh_movie_list(
+    movie_lists: web::Data<std::sync::RwLock<kodi_helper::MovieLists>>,
+    history: web::Data<std::sync::RwLock<kodi_helper::cache::History>>,
+    kodi_clients: web::Data<std::sync::RwLock<kodi_helper::kodi_rpc::KodiClients>>,
+) {
+    kodi_helper::update_movie_list(movie_lists.clone(), history.clone(), kodi_clients).await;
+}
+
+#[instrument(skip(tvshow_lists, kodi_clients), level = "info")]
+async fn refresh_tvshow_list(
+    tvshow_lists: web::Data<std::sync::RwLock<kodi_helper::TvShowLists>>,
+    kodi_clients: web::Data<std::sync::RwLock<kodi_helper::kodi_rpc::KodiClients>>,
+) {
+    kodi_helper::update_tvshow_list(tvshow_lists.clone(), kodi_clients).await;
 }
 
-#[instrument(skip(movie_list), level = "info")]
-async fn refresh_movie_list(movie_list: web::Data<std::sync::RwLock<Vec<kodi_helper::Movie>>>) {
-    kodi_helper::update_movie_list(movie_list.clone()).await;
+#[instrument(skip(music_libraries, kodi_clients), level = "info")]
+async fn refresh_music_library(
+    music_libraries: web::Data<std::sync::RwLock<kodi_helper::MusicLibraries>>,
+    kodi_clients: web::Data<std::sync::RwLock<kodi_helper::kodi_rpc::KodiClients>>,
+) {
+    kodi_helper::update_music_library(music_libraries.clone(), kodi_clients).await;
 }
 
 fn setup_server(
-    movie_list: web::Data<std::sync::RwLock<Vec<kodi_helper::Movie>>>,
+    movie_lists: web::Data<std::sync::RwLock<kodi_helper::MovieLists>>,
+    history: web::Data<std::sync::RwLock<kodi_helper::cache::History>>,
+    tvshow_lists: web::Data<std::sync::RwLock<kodi_helper::TvShowLists>>,
+    music_libraries: web::Data<std::sync::RwLock<kodi_helper::MusicLibraries>>,
+    kodi_clients: web::Data<std::sync::RwLock<kodi_helper::kodi_rpc::KodiClients>>,
 ) -> std::io::Result<actix_web::dev::Server> {
-    Ok(HttpServer::new(move || {
+    let server_config = kodi_helper::CONFIG.read().unwrap().server.clone();
+    let prefix = server_config.route_prefix();
+    let static_dir = server_config.static_dir.clone();
+
+    let mut server = HttpServer::new(move || {
         App::new()
-            .app_data(movie_list.clone())
+            .app_data(movie_lists.clone())
+            .app_data(history.clone())
+            .app_data(tvshow_lists.clone())
+            .app_data(music_libraries.clone())
+            .app_data(kodi_clients.clone())
+            .wrap_fn({
+                // routes are mounted under the prefix of the startup, whatever the reloads
+                let prefix = prefix.clone();
+                move |req, srv| match kodi_helper::api::auth::check_access(&req, &prefix) {
+                    Ok(()) => futures::future::Either::Left(srv.call(req)),
+                    Err(response) => futures::future::Either::Right(futures::future::ok(
+                        req.into_response(response),
+                    )),
+                }
+            })
             .wrap(middleware::Logger::default())
-            // API
-            .service(web::resource("/api/config").to(kodi_helper::api::config::get_config))
-            .service(
-                web::resource("/api/movies")
-                    .route(web::get().to(kodi_helper::api::movies::get_movie_list))
-                    .route(web::delete().to(kodi_helper::api::movies::clean_and_scan_kodi_library))
-                    .route(web::put().to(kodi_helper::api::movies::update_movie_list)),
-            )
             .service(
-                web::resource("/api/movies/{movie_id}")
-                    .route(web::delete().to(kodi_helper::api::movie::refresh_movie))
-                    .route(web::put().to(kodi_helper::api::movie::set_movie_tags)),
+                web::scope(&prefix)
+                    // API
+                    .service(
+                        web::resource("/api/login")
+                            .route(web::post().to(kodi_helper::api::auth::login)),
+                    )
+                    .service(
+                        web::resource("/api/logout")
+                            .route(web::post().to(kodi_helper::api::auth::logout)),
+                    )
+                    .service(web::resource("/api/session").to(kodi_helper::api::auth::get_session))
+                    .service(
+                        web::resource("/api/config")
+                            .route(web::get().to(kodi_helper::api::config::get_config))
+                            .route(web::put().to(kodi_helper::api::config::update_config)),
+                    )
+                    .service(web::resource("/api/kodis").to(kodi_helper::api::kodis::get_kodi_list))
+                    .service(
+                        web::resource("/api/history").to(kodi_helper::api::history::get_history),
+                    )
+                    .service(
+                        web::resource("/api/events").to(kodi_helper::api::events::get_event_stream),
+                    )
+                    .service(
+                        web::resource("/api/jobs")
+                            .route(web::get().to(kodi_helper::api::jobs::get_job_list))
+                            .route(web::post().to(kodi_helper::api::jobs::start_job)),
+                    )
+                    .service(
+                        web::resource("/api/jobs/{job_id}")
+                            .route(web::get().to(kodi_helper::api::jobs::get_job))
+                            .route(web::delete().to(kodi_helper::api::jobs::cancel_job)),
+                    )
+                    .service(
+                        web::resource("/api/movies/merged")
+                            .to(kodi_helper::api::movies::get_merged_movie_list),
+                    )
+                    .service(
+                        web::resource("/api/movies")
+                            .route(web::get().to(kodi_helper::api::movies::get_movie_list))
+                            .route(
+                                web::delete()
+                                    .to(kodi_helper::api::movies::clean_and_scan_kodi_library),
+                            )
+                            .route(web::put().to(kodi_helper::api::movies::update_movie_list)),
+                    )
+                    .service(
+                        web::resource("/api/movies/{movie_id}/file")
+                            .route(web::post().to(kodi_helper::api::movie::file_action)),
+                    )
+                    .service(
+                        web::resource("/api/movies/{movie_id}")
+                            .route(web::delete().to(kodi_helper::api::movie::refresh_movie))
+                            .route(web::put().to(kodi_helper::api::movie::set_movie_tags)),
+                    )
+                    .service(
+                        web::resource("/api/stats/quality")
+                            .to(kodi_helper::api::stats::get_quality_stats),
+                    )
+                    .service(
+                        web::resource("/api/hospital")
+                            .to(kodi_helper::api::errors::get_hospital_report),
+                    )
+                    .service(
+                        web::resource("/api/errors/duplicates")
+                            .to(kodi_helper::api::errors::get_duplicate_movies_list),
+                    )
+                    .service(
+                        web::resource("/api/errors/upgrades")
+                            .to(kodi_helper::api::errors::get_upgrade_candidates),
+                    )
+                    .service(
+                        web::resource("/api/errors/orphans")
+                            .to(kodi_helper::api::errors::get_orphan_movies),
+                    )
+                    .service(
+                        web::resource("/api/errors/recognition")
+                            .to(kodi_helper::api::errors::get_recognition_errors_list),
+                    )
+                    .service(
+                        web::resource("/api/errors/missing")
+                            .to(kodi_helper::api::errors::get_unrecognized_movies),
+                    )
+                    .service(
+                        web::resource("/api/tvshows")
+                            .route(web::get().to(kodi_helper::api::tvshows::get_tvshow_list))
+                            .route(web::put().to(kodi_helper::api::tvshows::update_tvshow_list)),
+                    )
+                    .service(
+                        web::resource("/api/tvshows/{tvshow_id}")
+                            .to(kodi_helper::api::tvshows::get_tvshow),
+                    )
+                    .service(
+                        web::resource("/api/errors/tvshows/missing-episodes")
+                            .to(kodi_helper::api::errors::get_missing_episodes_list),
+                    )
+                    .service(
+                        web::resource("/api/errors/tvshows/duplicates")
+                            .to(kodi_helper::api::errors::get_duplicate_episodes_list),
+                    )
+                    .service(
+                        web::resource("/api/errors/tvshows/missing")
+                            .to(kodi_helper::api::errors::get_unrecognized_episodes),
+                    )
+                    .service(
+                        web::resource("/api/music")
+                            .route(web::get().to(kodi_helper::api::music::get_music_library))
+                            .route(web::put().to(kodi_helper::api::music::update_music_library)),
+                    )
+                    .service(
+                        web::resource("/api/music/artists")
+                            .to(kodi_helper::api::music::get_artist_list),
+                    )
+                    .service(
+                        web::resource("/api/music/albums")
+                            .to(kodi_helper::api::music::get_album_list),
+                    )
+                    .service(
+                        web::resource("/api/music/songs")
+                            .to(kodi_helper::api::music::get_song_list),
+                    )
+                    .service(
+                        web::resource("/api/errors/music/missing-art")
+                            .to(kodi_helper::api::errors::get_albums_without_art_list),
+                    )
+                    .service(
+                        web::resource("/api/errors/music/songs-without-album")
+                            .to(kodi_helper::api::errors::get_songs_without_album_list),
+                    )
+                    .service(
+                        web::resource("/api/errors/music/duplicates")
+                            .to(kodi_helper::api::errors::get_duplicate_albums_list),
+                    )
+                    .service(
+                        web::resource("/api/errors/divergence")
+                            .to(kodi_helper::api::errors::get_divergent_movies_list),
+                    )
+                    .service(
+                        web::resource("/api/kodis/{kodi}/sync")
+                            .route(web::post().to(kodi_helper::api::kodis::sync_kodi_library)),
+                    )
+                    .service(
+                        web::resource("/api/kodis/{kodi}/jobs")
+                            .route(web::post().to(kodi_helper::api::jobs::start_job)),
+                    )
+                    .service(
+                        web::resource("/api/kodis/{kodi}/history")
+                            .to(kodi_helper::api::history::get_kodi_history),
+                    )
+                    .service(
+                        web::resource("/api/kodis/{kodi}/movies")
+                            .route(web::get().to(kodi_helper::api::movies::get_movie_list))
+                            .route(
+                                web::delete()
+                                    .to(kodi_helper::api::movies::clean_and_scan_kodi_library),
+                            )
+                            .route(web::put().to(kodi_helper::api::movies::update_movie_list)),
+                    )
+                    .service(
+                        web::resource("/api/kodis/{kodi}/movies/{movie_id}/file")
+                            .route(web::post().to(kodi_helper::api::movie::file_action)),
+                    )
+                    .service(
+                        web::resource("/api/kodis/{kodi}/movies/{movie_id}")
+                            .route(web::delete().to(kodi_helper::api::movie::refresh_movie))
+                            .route(web::put().to(kodi_helper::api::movie::set_movie_tags)),
+                    )
+                    .service(
+                        web::resource("/api/kodis/{kodi}/stats/quality")
+                            .to(kodi_helper::api::stats::get_quality_stats),
+                    )
+                    .service(
+                        web::resource("/api/kodis/{kodi}/hospital")
+                            .to(kodi_helper::api::errors::get_hospital_report),
+                    )
+                    .service(
+                        web::resource("/api/kodis/{kodi}/errors/duplicates")
+                            .to(kodi_helper::api::errors::get_duplicate_movies_list),
+                    )
+                    .service(
+                        web::resource("/api/kodis/{kodi}/errors/upgrades")
+                            .to(kodi_helper::api::errors::get_upgrade_candidates),
+                    )
+                    .service(
+                        web::resource("/api/kodis/{kodi}/errors/orphans")
+                            .to(kodi_helper::api::errors::get_orphan_movies),
+                    )
+                    .service(
+                        web::resource("/api/kodis/{kodi}/errors/recognition")
+                            .to(kodi_helper::api::errors::get_recognition_errors_list),
+                    )
+                    .service(
+                        web::resource("/api/kodis/{kodi}/errors/missing")
+                            .to(kodi_helper::api::errors::get_unrecognized_movies),
+                    )
+                    .service(
+                        web::resource("/api/kodis/{kodi}/tvshows")
+                            .route(web::get().to(kodi_helper::api::tvshows::get_tvshow_list))
+                            .route(web::put().to(kodi_helper::api::tvshows::update_tvshow_list)),
+                    )
+                    .service(
+                        web::resource("/api/kodis/{kodi}/tvshows/{tvshow_id}")
+                            .to(kodi_helper::api::tvshows::get_tvshow),
+                    )
+                    .service(
+                        web::resource("/api/kodis/{kodi}/errors/tvshows/missing-episodes")
+                            .to(kodi_helper::api::errors::get_missing_episodes_list),
+                    )
+                    .service(
+                        web::resource("/api/kodis/{kodi}/errors/tvshows/duplicates")
+                            .to(kodi_helper::api::errors::get_duplicate_episodes_list),
+                    )
+                    .service(
+                        web::resource("/api/kodis/{kodi}/errors/tvshows/missing")
+                            .to(kodi_helper::api::errors::get_unrecognized_episodes),
+                    )
+                    .service(
+                        web::resource("/api/kodis/{kodi}/music")
+                            .route(web::get().to(kodi_helper::api::music::get_music_library))
+                            .route(web::put().to(kodi_helper::api::music::update_music_library)),
+                    )
+                    .service(
+                        web::resource("/api/kodis/{kodi}/music/artists")
+                            .to(kodi_helper::api::music::get_artist_list),
+                    )
+                    .service(
+                        web::resource("/api/kodis/{kodi}/music/albums")
+                            .to(kodi_helper::api::music::get_album_list),
+                    )
+                    .service(
+                        web::resource("/api/kodis/{kodi}/music/songs")
+                            .to(kodi_helper::api::music::get_song_list),
+                    )
+                    .service(
+                        web::resource("/api/kodis/{kodi}/errors/music/missing-art")
+                            .to(kodi_helper::api::errors::get_albums_without_art_list),
+                    )
+                    .service(
+                        web::resource("/api/kodis/{kodi}/errors/music/songs-without-album")
+                            .to(kodi_helper::api::errors::get_songs_without_album_list),
+                    )
+                    .service(
+                        web::resource("/api/kodis/{kodi}/errors/music/duplicates")
+                            .to(kodi_helper::api::errors::get_duplicate_albums_list),
+                    )
+                    // UI
+                    .service(Files::new("/static", &static_dir))
+                    .default_service(web::get().to(serve_index)),
             )
-            .service(
-                web::resource("/api/errors/duplicates")
-                    .to(kodi_helper::api::errors::get_duplicate_movies_list),
-            )
-            .service(
-                web::resource("/api/errors/recognition")
-                    .to(kodi_helper::api::errors::get_recognition_errors_list),
-            )
-            .service(
-                web::resource("/api/errors/missing")
-                    .to(kodi_helper::api::errors::get_unrecognized_movies),
-            )
-            // UI
-            .service(Files::new("/static", "./static/").index_file("index.html"))
-            .service(Files::new("/{tail:.*}", "./static/").index_file("index.html"))
-    })
-    .bind("0.0.0.0:8080")?
-    .run())
+    });
+
+    let tls_config = server_config
+        .tls
+        .as_ref()
+        .map(load_tls_config)
+        .transpose()?;
+    for address in server_config.bind_addresses.iter() {
+        let address = (address.as_str(), server_config.port);
+        server = match tls_config.as_ref() {
+            Some(tls_config) => server.bind_rustls(address, tls_config.clone())?,
+            None => server.bind(address)?,
+        };
+    }
+    Ok(server.run())
 }
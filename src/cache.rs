@@ -0,0 +1,254 @@
+use lazy_static::lazy_static;
+use serde::{Deserialize, Serialize};
+use tracing::{event, instrument, Level};
+
+use crate::{Movie, MovieLists};
+
+lazy_static! {
+    /// Held while saving
+    static ref SAVING: std::sync::Mutex<()> = std::sync::Mutex::new(());
+}
+
+/// Number of history entries kept, older entries are dropped
+const MAX_HISTORY_ENTRIES: usize = 500;
+
+/// History of the changes of the movie lists, newest first
+pub type History = Vec<HistoryEntry>;
+
+/// Last known state of the library, saved to disk between runs
+#[derive(Deserialize, Serialize, Clone, Debug, Default)]
+pub struct Snapshot {
+    pub movies: MovieLists,
+    pub history: History,
+}
+
+/// Changes of the movie list of a Kodi instance during a refresh
+#[derive(Deserialize, Serialize, Clone, Debug)]
+pub struct HistoryEntry {
+    /// Seconds since UNIX epoch
+    pub timestamp: u64,
+    pub kodi: String,
+    pub added: Vec<MovieSummary>,
+    pub removed: Vec<MovieSummary>,
+    pub changed: Vec<ChangedMovie>,
+}
+
+#[derive(Deserialize, Serialize, Clone, Debug)]
+pub struct MovieSummary {
+    pub id: u16,
+    pub title: String,
+}
+
+#[derive(Deserialize, Serialize, Clone, Debug)]
+pub struct ChangedMovie {
+    pub id: u16,
+    pub title: String,
+    pub fields: Vec<String>,
+}
+
+impl From<&Movie> for MovieSummary {
+    fn from(movie: &Movie) -> Self {
+        MovieSummary {
+            id: movie.id,
+            title: movie.title.clone(),
+        }
+    }
+}
+
+fn changed_fields(old: &Movie, new: &Movie) -> Vec<String> {
+    let mut fields = vec![];
+    let mut check = |name: &str, changed: bool| {
+        if changed {
+            fields.push(name.to_string());
+        }
+    };
+    check("title", old.title != new.title);
+    check("runtime", old.runtime != new.runtime);
+    check("path", old.path != new.path);
+    check("premiered", old.premiered != new.premiered);
+    check("resolution", old.resolution != new.resolution);
+    check("poster", old.poster != new.poster);
+    check("fanart", old.fanart != new.fanart);
+    check("set_poster", old.set_poster != new.set_poster);
+    check("set_fanart", old.set_fanart != new.set_fanart);
+    check("rating", old.rating != new.rating);
+    check("playcount", old.playcount != new.playcount);
+    check("set", old.set != new.set);
+    check("tags", old.tags != new.tags);
+    check("genres", old.genres != new.genres);
+    check("cast", old.cast != new.cast);
+    check("plot", old.plot != new.plot);
+    check("video_codec", old.video_codec != new.video_codec);
+    check("hdr", old.hdr != new.hdr);
+    check("audio", old.audio != new.audio);
+    check(
+        "subtitle_languages",
+        old.subtitle_languages != new.subtitle_languages,
+    );
+    fields
+}
+
+/// Compare two movie lists of a Kodi instance, returning `None` if nothing changed
+pub fn diff_movie_lists(kodi: &str, old: &[Movie], new: &[Movie]) -> Option<HistoryEntry> {
+    let old_by_id = old
+        .iter()
+        .map(|movie| (movie.id, movie))
+        .collect::<std::collections::HashMap<_, _>>();
+    let new_ids = new
+        .iter()
+        .map(|movie| movie.id)
+        .collect::<std::collections::HashSet<_>>();
+
+    let added = new
+        .iter()
+        .filter(|movie| !old_by_id.contains_key(&movie.id))
+        .map(MovieSummary::from)
+        .collect::<Vec<_>>();
+    let removed = old
+        .iter()
+        .filter(|movie| !new_ids.contains(&movie.id))
+        .map(MovieSummary::from)
+        .collect::<Vec<_>>();
+    let changed = new
+        .iter()
+        .filter_map(|movie| {
+            let fields = changed_fields(old_by_id.get(&movie.id)?, movie);
+            if fields.is_empty() {
+                None
+            } else {
+                Some(ChangedMovie {
+                    id: movie.id,
+                    title: movie.title.clone(),
+                    fields,
+                })
+            }
+        })
+        .collect::<Vec<_>>();
+
+    if added.is_empty() && removed.is_empty() && changed.is_empty() {
+        return None;
+    }
+    Some(HistoryEntry {
+        timestamp: std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .map(|duration| duration.as_secs())
+            .unwrap_or(0),
+        kodi: kodi.to_string(),
+        added,
+        removed,
+        changed,
+    })
+}
+
+/// Add an entry at the start of the history, dropping the oldest entries
+pub fn record(history: &mut History, entry: HistoryEntry) {
+    history.insert(0, entry);
+    history.truncate(MAX_HISTORY_ENTRIES);
+}
+
+/// Load a snapshot, starting with an empty one if the file doesn't exist or can't be read
+#[instrument(level = "info")]
+pub fn load(path: &str) -> Snapshot {
+    match std::fs::read(path).map(|data| serde_json::from_slice::<Snapshot>(&data)) {
+        Ok(Ok(snapshot)) => {
+            event!(
+                Level::INFO,
+                "loaded cached movie lists: {}",
+                snapshot.movies.len()
+            );
+            snapshot
+        }
+        Ok(Err(err)) => {
+            event!(Level::WARN, "invalid cache file {}: {}", path, err);
+            Snapshot::default()
+        }
+        Err(_) => Snapshot::default(),
+    }
+}
+
+/// Save a snapshot, writing to a temporary file first so that a crash doesn't corrupt the cache
+#[instrument(skip(snapshot), err, level = "info")]
+pub fn save(path: &str, snapshot: &Snapshot) -> Result<(), Box<dyn std::error::Error>> {
+    // concurrent refreshes would write to the same temporary file
+    let _saving = SAVING.lock().unwrap();
+    let tmp_path = format!("{}.tmp", path);
+    std::fs::write(&tmp_path, serde_json::to_vec(snapshot)?)?;
+    std::fs::rename(&tmp_path, path)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn movie(id: u16, title: &str, playcount: u8) -> Movie {
+        crate::tests::movie(serde_json::json!({
+            "id": id,
+            "title": title,
+            "playcount": playcount,
+        }))
+    }
+
+    fn ids(summaries: &[MovieSummary]) -> Vec<u16> {
+        summaries.iter().map(|summary| summary.id).collect()
+    }
+
+    #[test]
+    fn diff_lists() {
+        let old = vec![
+            movie(1, "Alien", 0),
+            movie(2, "Brazil", 0),
+            movie(3, "Heat", 1),
+        ];
+        let new = vec![
+            movie(1, "Alien", 0),
+            movie(3, "Heat", 2),
+            movie(4, "Jaws", 0),
+        ];
+
+        let entry = diff_movie_lists("living room", &old, &new).unwrap();
+        assert_eq!(entry.kodi, "living room");
+        assert_eq!(ids(&entry.added), vec![4]);
+        assert_eq!(ids(&entry.removed), vec![2]);
+        assert_eq!(entry.changed.len(), 1);
+        assert_eq!(entry.changed[0].id, 3);
+        assert_eq!(entry.changed[0].fields, vec!["playcount".to_string()]);
+
+        // a movie changing its title keeps its id, it's not added and removed
+        let renamed = vec![movie(1, "Alien: Director's Cut", 0)];
+        let entry = diff_movie_lists("living room", &old[..1], &renamed).unwrap();
+        assert!(entry.added.is_empty() && entry.removed.is_empty());
+        assert_eq!(entry.changed[0].fields, vec!["title".to_string()]);
+
+        assert!(diff_movie_lists("living room", &old, &old).is_none());
+        assert!(diff_movie_lists("living room", &[], &[]).is_none());
+        assert_eq!(
+            ids(&diff_movie_lists("living room", &[], &old).unwrap().added),
+            vec![1, 2, 3]
+        );
+        assert_eq!(
+            ids(&diff_movie_lists("living room", &old, &[]).unwrap().removed),
+            vec![1, 2, 3]
+        );
+    }
+
+    #[test]
+    fn history_is_capped() {
+        let entry = |timestamp: u64| HistoryEntry {
+            timestamp,
+            kodi: "living room".to_string(),
+            added: vec![],
+            removed: vec![],
+            changed: vec![],
+        };
+        let mut history = History::new();
+        for timestamp in 0..(MAX_HISTORY_ENTRIES as u64 + 10) {
+            record(&mut history, entry(timestamp));
+        }
+
+        assert_eq!(history.len(), MAX_HISTORY_ENTRIES);
+        // newest first, the oldest are dropped
+        assert_eq!(history[0].timestamp, MAX_HISTORY_ENTRIES as u64 + 9);
+        assert_eq!(history[MAX_HISTORY_ENTRIES - 1].timestamp, 10);
+    }
+}
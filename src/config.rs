@@ -0,0 +1,482 @@
+use lazy_static::lazy_static;
+use serde::Serialize;
+use tracing::{event, Level};
+
+use crate::events::{Event, EVENTS};
+use crate::kodi_rpc::{KodiClients, KodiRPC};
+use crate::{Config, ServerConfig, CONFIG};
+
+lazy_static! {
+    /// File the configuration was loaded from, next to which changes are saved
+    pub static ref CONFIG_FILE: std::sync::RwLock<Option<String>> = std::sync::RwLock::new(None);
+    /// Server settings of the configuration file, without the command line overrides
+    pub static ref FILE_SERVER: std::sync::RwLock<Option<ServerConfig>> =
+        std::sync::RwLock::new(None);
+}
+
+/// Time between two checks of the configuration file
+const WATCH_INTERVAL: std::time::Duration = std::time::Duration::from_secs(5);
+
+/// Something wrong in the configuration
+#[derive(Serialize, Clone, Debug)]
+pub struct Problem {
+    pub field: String,
+    pub message: String,
+    /// Line of the configuration file where the problem is, when it can be found
+    pub line: Option<usize>,
+    /// Value to look for in the configuration file to find the line
+    #[serde(skip)]
+    value: Option<String>,
+}
+
+impl Problem {
+    fn new(field: &str, value: Option<&str>, message: String) -> Problem {
+        Problem {
+            field: field.to_string(),
+            message,
+            line: None,
+            value: value.map(|value| value.to_string()),
+        }
+    }
+}
+
+impl std::fmt::Display for Problem {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if let Some(line) = self.line {
+            write!(f, "line {}: {}: {}", line, self.field, self.message)
+        } else {
+            write!(f, "{}: {}", self.field, self.message)
+        }
+    }
+}
+
+/// Find the lines of the problems in the source of the configuration, from the lines with their
+/// value as written or escaped, narrowed to the lines where their key is set, or from the lines
+/// where their key is set. The line is left out when several lines match, as the source is not
+/// parsed and a wrong line would be misleading.
+pub fn locate(problems: &mut [Problem], source: &str) {
+    let lines = source.lines().collect::<Vec<_>>();
+    let matching = |matches: &dyn Fn(&str) -> bool| {
+        lines
+            .iter()
+            .enumerate()
+            .filter(|(_, line)| matches(line))
+            .map(|(index, _)| index + 1)
+            .collect::<Vec<_>>()
+    };
+    let unique = |candidates: &[usize]| match candidates {
+        [line] => Some(*line),
+        _ => None,
+    };
+    for problem in problems.iter_mut() {
+        let key = problem
+            .field
+            .rsplit('.')
+            .next()
+            .and_then(|key| key.split('[').next())
+            .unwrap_or_default()
+            .to_string();
+        let key_lines = match regex::Regex::new(&format!(
+            r#"(^|[\s{{,.])"?{}"?\s*[:={{]"#,
+            regex::escape(&key)
+        )) {
+            Ok(setting) if !key.is_empty() => matching(&|line| setting.is_match(line)),
+            _ => vec![],
+        };
+        let value_lines = problem
+            .value
+            .as_ref()
+            .filter(|value| !value.is_empty())
+            .map(|value| {
+                let escaped = value.replace('\\', "\\\\");
+                matching(&|line| line.contains(value.as_str()) || line.contains(&escaped))
+            })
+            .unwrap_or_default();
+        problem.line = if value_lines.is_empty() {
+            unique(&key_lines)
+        } else {
+            unique(&value_lines).or_else(|| {
+                unique(
+                    &value_lines
+                        .iter()
+                        .filter(|line| key_lines.contains(line))
+                        .copied()
+                        .collect::<Vec<_>>(),
+                )
+            })
+        };
+    }
+}
+
+/// File where the changes made with the API are saved, next to the configuration file which is
+/// left as written
+pub fn overrides_path(path: &str) -> String {
+    format!("{}.overrides.json", path)
+}
+
+/// Read a configuration file and check it can be used. The changes saved from the API are
+/// applied over it.
+pub fn load(path: &str) -> Result<Config, Vec<Problem>> {
+    let overrides = overrides_path(path);
+    let config: Config = hocon::HoconLoader::new()
+        .load_file(path)
+        .and_then(|hc| {
+            if std::path::Path::new(&overrides).exists() {
+                hc.load_file(&overrides)
+            } else {
+                Ok(hc)
+            }
+        })
+        .and_then(|hc| hc.resolve())
+        .map_err(|err| vec![Problem::new(path, None, format!("can't load: {}", err))])?;
+    validate(&config).map_err(|mut problems| {
+        if let Ok(source) = std::fs::read_to_string(path) {
+            locate(&mut problems, &source);
+        }
+        problems
+    })?;
+    Ok(config)
+}
+
+/// Check every pattern of the configuration, returning all the problems found
+pub fn validate(config: &Config) -> Result<(), Vec<Problem>> {
+    let mut problems = vec![];
+    for (i, pattern) in config.filepatterns_to_ignore.iter().enumerate() {
+        if let Err(err) = regex::Regex::new(pattern) {
+            problems.push(Problem::new(
+                &format!("filepatterns_to_ignore[{}]", i),
+                Some(pattern),
+                err.to_string(),
+            ));
+        }
+    }
+    if let Some(pattern) = config.movie_pattern.as_ref() {
+        if let Err(err) = crate::filename::check_pattern(pattern) {
+            problems.push(Problem::new("movie_pattern", Some(pattern), err));
+        }
+    }
+    for (i, pattern) in config.movie_patterns.iter().enumerate() {
+        if let Err(err) = crate::filename::check_pattern(pattern) {
+            problems.push(Problem::new(
+                &format!("movie_patterns[{}]", i),
+                Some(pattern),
+                err,
+            ));
+        }
+    }
+    if config.movies_directory.is_none() && config.movie_sources.is_empty() {
+        problems.push(Problem::new(
+            "movie_sources",
+            None,
+            "at least one movie source or a movies_directory is needed".to_string(),
+        ));
+    }
+    for (i, source) in config.movie_sources.iter().enumerate() {
+        for (j, pattern) in source.filepatterns_to_ignore.iter().enumerate() {
+            if let Err(err) = regex::Regex::new(pattern) {
+                problems.push(Problem::new(
+                    &format!("movie_sources[{}].filepatterns_to_ignore[{}]", i, j),
+                    Some(pattern),
+                    err.to_string(),
+                ));
+            }
+        }
+        for (j, pattern) in source.movie_patterns.iter().enumerate() {
+            if let Err(err) = crate::filename::check_pattern(pattern) {
+                problems.push(Problem::new(
+                    &format!("movie_sources[{}].movie_patterns[{}]", i, j),
+                    Some(pattern),
+                    err,
+                ));
+            }
+        }
+    }
+    for (i, rule) in config.language_rules.iter().enumerate() {
+        if rule.audio.is_empty() && rule.subtitles.is_empty() {
+            problems.push(Problem::new(
+                &format!("language_rules[{}]", i),
+                Some(&rule.name),
+                "a language rule needs audio or subtitles languages".to_string(),
+            ));
+        }
+    }
+    if config.kodis.is_empty() {
+        problems.push(Problem::new(
+            "kodis",
+            None,
+            "at least one Kodi instance is needed".to_string(),
+        ));
+    }
+    for (i, kodi) in config.kodis.iter().enumerate() {
+        if reqwest::Url::parse(&kodi.url).is_err() {
+            problems.push(Problem::new(
+                &format!("kodis[{}].url", i),
+                Some(&kodi.url),
+                format!("invalid url {}", kodi.url),
+            ));
+        }
+    }
+
+    if problems.is_empty() {
+        Ok(())
+    } else {
+        Err(problems)
+    }
+}
+
+/// Check that every Kodi instance answers, and knows the configured directories
+pub async fn check_kodis(config: &Config) -> Vec<Problem> {
+    let mut problems = vec![];
+    for (i, kodi) in config.kodis.iter().enumerate() {
+        let field = format!("kodis[{}].url", i);
+        let kodi_rpc = match KodiRPC::new(kodi) {
+            Ok(kodi_rpc) => kodi_rpc,
+            Err(err) => {
+                problems.push(Problem::new(&field, Some(&kodi.url), err.to_string()));
+                continue;
+            }
+        };
+        if let Err(err) = kodi_rpc.ping().await {
+            problems.push(Problem::new(
+                &field,
+                Some(&kodi.url),
+                format!("{} does not answer: {}", kodi.name, err),
+            ));
+            continue;
+        }
+        match kodi_rpc.get_version().await {
+            Ok(version) => event!(
+                Level::INFO,
+                "{} answers with JSON-RPC version {}",
+                kodi.name,
+                version
+            ),
+            Err(err) => problems.push(Problem::new(
+                &field,
+                Some(&kodi.url),
+                format!("{} does not give its version: {}", kodi.name, err),
+            )),
+        }
+
+        let directories = config
+            .movies_directory
+            .iter()
+            .map(|directory| ("movies_directory".to_string(), directory))
+            .chain(
+                config
+                    .movie_sources
+                    .iter()
+                    .enumerate()
+                    .map(|(i, source)| (format!("movie_sources[{}].path", i), &source.path)),
+            )
+            .chain(
+                config
+                    .tvshows_directory
+                    .iter()
+                    .map(|directory| ("tvshows_directory".to_string(), directory)),
+            );
+        for (directory_field, directory) in directories {
+            if let Err(err) = kodi_rpc.get_directory(directory).await {
+                problems.push(Problem::new(
+                    &directory_field,
+                    Some(directory),
+                    format!("{} can't list {}: {}", kodi.name, directory, err),
+                ));
+            }
+        }
+    }
+    problems
+}
+
+/// Check that the local folders of the path mappings can be read
+pub fn check_path_mappings(config: &Config) -> Vec<Problem> {
+    config
+        .path_mappings
+        .iter()
+        .enumerate()
+        .filter_map(|(i, mapping)| {
+            std::fs::read_dir(&mapping.local).err().map(|err| {
+                Problem::new(
+                    &format!("path_mappings[{}].local", i),
+                    Some(&mapping.local),
+                    format!("can't read {}: {}", mapping.local, err),
+                )
+            })
+        })
+        .collect()
+}
+
+/// Check a configuration file, every Kodi instance it uses and its local folders
+pub async fn check(path: &str) -> Vec<Problem> {
+    let config = match load(path) {
+        Ok(config) => config,
+        Err(problems) => return problems,
+    };
+    let mut problems = check_kodis(&config).await;
+    problems.extend(check_path_mappings(&config));
+    if let Ok(source) = std::fs::read_to_string(path) {
+        locate(&mut problems, &source);
+    }
+    problems
+}
+
+/// Replace the current configuration, and the HTTP clients of the Kodi instances. Server
+/// settings are only read on startup, and new Kodi instances are only followed for notifications
+/// after a restart.
+pub fn apply(mut config: Config, kodi_clients: &std::sync::RwLock<KodiClients>) {
+    let clients = KodiClients::from_config(&config);
+    *kodi_clients.write().unwrap() = clients;
+    let mut current = CONFIG.write().unwrap();
+    let mut file_server = FILE_SERVER.write().unwrap();
+    let previous = file_server.as_ref().unwrap_or(&current.server);
+    if previous.route_prefix() != config.server.route_prefix()
+        || previous.bind_addresses != config.server.bind_addresses
+        || previous.port != config.server.port
+    {
+        event!(
+            Level::WARN,
+            "server settings changed, they will be used after a restart"
+        );
+    }
+    *file_server = Some(config.server.clone());
+    // the running server keeps its settings, with the command line overrides
+    config.server = current.server.clone();
+    *current = config;
+}
+
+/// Keep the secrets of the current configuration when they are not in the new one, as they are
+/// never sent by the API
+pub fn keep_secrets(config: &mut Config, current: &Config) {
+    if config.auth.is_none() {
+        config.auth = current.auth.clone();
+    }
+    for kodi in config.kodis.iter_mut() {
+        if kodi.password.is_none() {
+            kodi.password = current
+                .get_kodi(&kodi.name)
+                .and_then(|known| known.password.clone());
+        }
+    }
+}
+
+/// Serialize a configuration with its secrets
+fn to_value(config: &Config) -> Result<serde_json::Value, serde_json::Error> {
+    let mut value = serde_json::to_value(config)?;
+    if let Some(auth) = config.auth.as_ref() {
+        value["auth"] = serde_json::to_value(auth)?;
+    }
+    for (i, kodi) in config.kodis.iter().enumerate() {
+        if let Some(password) = kodi.password.as_ref() {
+            value["kodis"][i]["password"] = password.clone().into();
+        }
+    }
+    Ok(value)
+}
+
+/// Save the settings of a configuration that differ from its file, next to it as JSON (valid
+/// HOCON), to keep the file as written. Secrets are included. Server settings are not saved, as
+/// they can be overridden from the command line.
+pub fn save(path: &str, config: &Config) -> Result<(), Box<dyn std::error::Error>> {
+    let from_file: Config = hocon::HoconLoader::new().load_file(path)?.resolve()?;
+    let from_file = to_value(&from_file)?;
+    let mut changes = serde_json::Map::new();
+    if let serde_json::Value::Object(values) = to_value(config)? {
+        for (key, value) in values {
+            if key != "server" && from_file.get(&key) != Some(&value) {
+                changes.insert(key, value);
+            }
+        }
+    }
+
+    let overrides = overrides_path(path);
+    if changes.is_empty() {
+        if std::path::Path::new(&overrides).exists() {
+            std::fs::remove_file(&overrides)?;
+        }
+        return Ok(());
+    }
+    let tmp_path = format!("{}.tmp", overrides);
+    std::fs::write(&tmp_path, serde_json::to_vec_pretty(&changes)?)?;
+    std::fs::rename(&tmp_path, &overrides)?;
+    Ok(())
+}
+
+fn modified(path: &str) -> Option<std::time::SystemTime> {
+    std::fs::metadata(path)
+        .and_then(|meta| meta.modified())
+        .ok()
+}
+
+/// Reload the configuration when its file or the changes saved next to it change. An invalid
+/// configuration is reported and ignored, the current one is kept.
+pub async fn watch(
+    path: String,
+    kodi_clients: actix_web::web::Data<std::sync::RwLock<KodiClients>>,
+) {
+    let overrides = overrides_path(&path);
+    let mut last_modified = (modified(&path), modified(&overrides));
+    loop {
+        actix_rt::time::delay_for(WATCH_INTERVAL).await;
+        let current_modified = (modified(&path), modified(&overrides));
+        if current_modified == last_modified {
+            continue;
+        }
+        last_modified = current_modified;
+
+        match load(&path) {
+            Ok(config) => {
+                event!(Level::INFO, "reloaded configuration from {}", path);
+                apply(config, &kodi_clients);
+                EVENTS.send(Event::ConfigReloaded { errors: vec![] });
+            }
+            Err(problems) => {
+                for problem in problems.iter() {
+                    event!(Level::ERROR, "invalid configuration: {}", problem);
+                }
+                EVENTS.send(Event::ConfigReloaded {
+                    errors: problems.iter().map(ToString::to_string).collect(),
+                });
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn locate_problems() {
+        let source = r#"kodis = [
+    { name: "living room", url: "http://kodi:8080/" }
+    { name: "bedroom", url: "http://kodi:8080/" }
+]
+movies_directory = "/movies/"
+# the cache_file is optional
+cache_file = "/tmp/cache.json"
+movie_pattern = "C:\\Movies\\(?P<title>.*)"
+"#;
+        let table = [
+            // unique value
+            ("movies_directory", Some("/movies/"), Some(5)),
+            // value on two lines, and key on both
+            ("kodis[1].url", Some("http://kodi:8080/"), None),
+            // value escaped in the source
+            ("movie_pattern", Some("C:\\Movies\\(?P<title>.*)"), Some(8)),
+            // value not in the source, key set once and only mentioned in a comment
+            ("cache_file", Some("/elsewhere"), Some(7)),
+            // key set on two lines
+            ("kodis[0].name", None, None),
+            // neither
+            ("path_mappings[0].local", Some("/mnt/"), None),
+        ];
+        let mut problems = table
+            .iter()
+            .map(|(field, value, _)| Problem::new(field, *value, "wrong".to_string()))
+            .collect::<Vec<_>>();
+        locate(&mut problems, source);
+        for (problem, (field, _, line)) in problems.iter().zip(table.iter()) {
+            assert_eq!(problem.line, *line, "{}", field);
+        }
+    }
+}
@@ -0,0 +1,131 @@
+use futures::channel::mpsc;
+use lazy_static::lazy_static;
+use serde::Serialize;
+use tracing::{event, Level};
+
+lazy_static! {
+    pub static ref EVENTS: Broadcaster = Broadcaster::default();
+}
+
+/// Something that happened in a library, sent to the UI
+#[derive(Serialize, Clone, Debug)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum Event {
+    MovieListRefreshed {
+        kodi: String,
+    },
+    CleanStarted {
+        kodi: String,
+    },
+    CleanFinished {
+        kodi: String,
+        success: bool,
+    },
+    ScanStarted {
+        kodi: String,
+    },
+    ScanFinished {
+        kodi: String,
+        success: bool,
+    },
+    TagsUpdated {
+        kodi: String,
+        movie_id: u16,
+        tags: Vec<String>,
+    },
+    JobStarted {
+        id: u64,
+        kodi: String,
+    },
+    JobFinished {
+        id: u64,
+        kodi: String,
+        status: crate::jobs::JobStatus,
+    },
+    /// The configuration changed, it was kept as is if there are errors
+    ConfigReloaded {
+        errors: Vec<String>,
+    },
+}
+
+impl Event {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Event::MovieListRefreshed { .. } => "movie_list_refreshed",
+            Event::CleanStarted { .. } => "clean_started",
+            Event::CleanFinished { .. } => "clean_finished",
+            Event::ScanStarted { .. } => "scan_started",
+            Event::ScanFinished { .. } => "scan_finished",
+            Event::TagsUpdated { .. } => "tags_updated",
+            Event::JobStarted { .. } => "job_started",
+            Event::JobFinished { .. } => "job_finished",
+            Event::ConfigReloaded { .. } => "config_reloaded",
+        }
+    }
+}
+
+/// Number of events waiting for a subscriber before it is considered too slow
+const SUBSCRIBER_BUFFER: usize = 64;
+
+/// Send events to every subscriber
+#[derive(Default)]
+pub struct Broadcaster {
+    subscribers: std::sync::Mutex<Vec<mpsc::Sender<Event>>>,
+}
+
+impl Broadcaster {
+    pub fn subscribe(&self) -> mpsc::Receiver<Event> {
+        let (sender, receiver) = mpsc::channel(SUBSCRIBER_BUFFER);
+        self.subscribers.lock().unwrap().push(sender);
+        receiver
+    }
+
+    /// Send an event, forgetting subscribers that are gone or that fell behind. The events of a
+    /// forgotten subscriber end, so that it can subscribe again and start from a fresh state.
+    pub fn send(&self, sent: Event) {
+        event!(Level::DEBUG, "sending event: {:?}", sent);
+        self.subscribers.lock().unwrap().retain_mut(|subscriber| {
+            match subscriber.try_send(sent.clone()) {
+                Ok(()) => true,
+                Err(err) => {
+                    if err.is_full() {
+                        event!(Level::WARN, "dropping an event subscriber that fell behind");
+                    }
+                    false
+                }
+            }
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use futures::StreamExt;
+
+    #[test]
+    fn lagging_subscribers_are_dropped() {
+        let broadcaster = Broadcaster::default();
+        let mut lagging = broadcaster.subscribe();
+        let gone = broadcaster.subscribe();
+        drop(gone);
+
+        let refreshed = || Event::MovieListRefreshed {
+            kodi: "living room".to_string(),
+        };
+        broadcaster.send(refreshed());
+        assert_eq!(broadcaster.subscribers.lock().unwrap().len(), 1);
+
+        let mut following = broadcaster.subscribe();
+        for _ in 0..SUBSCRIBER_BUFFER * 2 {
+            broadcaster.send(refreshed());
+            // a subscriber reading its events keeps up
+            assert!(following.try_next().unwrap().is_some());
+        }
+        assert_eq!(broadcaster.subscribers.lock().unwrap().len(), 1);
+
+        // the lagging subscriber gets what was buffered, then its events end
+        let received = futures::executor::block_on(lagging.by_ref().collect::<Vec<_>>());
+        assert!(!received.is_empty() && received.len() <= SUBSCRIBER_BUFFER + 2);
+    }
+}
@@ -0,0 +1,649 @@
+use lazy_static::lazy_static;
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+use tracing::{event, instrument, Level};
+
+use crate::filename::SourceParsers;
+use crate::kodi_rpc::{KodiRPC, KodiRpcError};
+use crate::sources::{movie_sources, Sources};
+use crate::{hospital, Config, Movie};
+
+lazy_static! {
+    /// Actions waiting to be confirmed, by confirmation token
+    static ref PENDING: std::sync::Mutex<std::collections::HashMap<String, Pending>> =
+        std::sync::Mutex::new(std::collections::HashMap::new());
+}
+
+/// Time to confirm an action after its preview
+const CONFIRMATION_DURATION: std::time::Duration = std::time::Duration::from_secs(10 * 60);
+
+/// Extensions of subtitles, which can have a language and flags before them, like
+/// "Title Year.en.forced.srt"
+const SUBTITLE_EXTENSIONS: &[&str] = &["srt", "sub", "idx", "ass", "ssa", "vtt", "smi", "sup"];
+/// Flags of subtitles besides their language
+const SUBTITLE_FLAGS: &[&str] = &["forced", "sdh", "cc", "hi", "default"];
+/// Extensions of artwork, alone or after its kind like "Title Year-poster.jpg"
+const IMAGE_EXTENSIONS: &[&str] = &["jpg", "jpeg", "png", "tbn"];
+
+#[derive(Deserialize, Serialize, Clone, Copy, Debug, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum Action {
+    /// Rename the file to "Title Year.ext"
+    Rename,
+    /// Move the file into the root folder of its movie source
+    Move,
+    /// Delete the file of a redundant duplicate
+    Delete,
+    /// Remove a movie whose file no longer exists from the library
+    Remove,
+}
+
+impl std::str::FromStr for Action {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "rename" => Ok(Action::Rename),
+            "move" => Ok(Action::Move),
+            "delete" => Ok(Action::Delete),
+            "remove" => Ok(Action::Remove),
+            _ => Err(format!("unknown action {}", s)),
+        }
+    }
+}
+
+impl std::fmt::Display for Action {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Action::Rename => write!(f, "rename"),
+            Action::Move => write!(f, "move"),
+            Action::Delete => write!(f, "delete"),
+            Action::Remove => write!(f, "remove"),
+        }
+    }
+}
+
+/// What an action will do, to be confirmed with its token
+#[derive(Serialize, Clone, Debug)]
+pub struct Plan {
+    pub kodi: String,
+    pub movie_id: u16,
+    pub action: Action,
+    /// The file as seen by Kodi
+    pub from: String,
+    /// Where the file will be as seen by Kodi, none when deleting or removing
+    pub to: Option<String>,
+    /// The local file, none when removing a movie from the library
+    pub local_from: Option<String>,
+    pub local_to: Option<String>,
+    /// Files next to the video named after it, like its .nfo, artwork and subtitles, renamed or
+    /// moved with it
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub sidecars: Vec<Sidecar>,
+    pub token: String,
+    /// What went wrong after the action was done, like Kodi failing to follow the file
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub warnings: Vec<String>,
+}
+
+/// A local file following the video of a movie
+#[derive(Serialize, Clone, Debug)]
+pub struct Sidecar {
+    pub local_from: String,
+    pub local_to: String,
+}
+
+struct Pending {
+    plan: Plan,
+    expires: std::time::Instant,
+}
+
+#[derive(Debug)]
+pub enum FileOperationError {
+    Kodi(KodiRpcError),
+    Io(std::io::Error),
+    /// The folder of a file is not in the path mappings, Kodi can't rename, move or delete files
+    NotMapped(String),
+    /// The file already is where the action would put it
+    NothingToDo(String),
+    /// Another file is already where the action would put the file
+    AlreadyExists(String),
+    /// The movie patterns would not find the title and year of the movie in its new name
+    Unrecognized(String),
+    /// Only redundant copies of a duplicated movie can be deleted
+    NotRedundant(u16),
+    /// Only movies whose file no longer exists can be removed from the library
+    NotOrphan(String),
+    /// The confirmation token is unknown, expired or for another action
+    InvalidToken,
+}
+
+impl FileOperationError {
+    /// Short name of the kind of error
+    pub fn kind(&self) -> &'static str {
+        match self {
+            FileOperationError::Kodi(err) => err.kind(),
+            FileOperationError::Io(_) => "io",
+            FileOperationError::NotMapped(_) => "not_mapped",
+            FileOperationError::NothingToDo(_) => "nothing_to_do",
+            FileOperationError::AlreadyExists(_) => "already_exists",
+            FileOperationError::Unrecognized(_) => "unrecognized",
+            FileOperationError::NotRedundant(_) => "not_redundant",
+            FileOperationError::NotOrphan(_) => "not_orphan",
+            FileOperationError::InvalidToken => "invalid_token",
+        }
+    }
+}
+
+impl std::fmt::Display for FileOperationError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            FileOperationError::Kodi(err) => write!(f, "{}", err),
+            FileOperationError::Io(err) => write!(f, "file operation failed: {}", err),
+            FileOperationError::NotMapped(path) => write!(
+                f,
+                "{} is not in a mapped folder, Kodi can't rename, move or delete files",
+                path
+            ),
+            FileOperationError::NothingToDo(path) => write!(f, "{} is already in place", path),
+            FileOperationError::AlreadyExists(path) => write!(f, "{} already exists", path),
+            FileOperationError::Unrecognized(path) => write!(
+                f,
+                "{} would not be recognized by the movie patterns, rename it by hand",
+                path
+            ),
+            FileOperationError::NotRedundant(movie_id) => write!(
+                f,
+                "movie {} is not a redundant copy of a duplicated movie",
+                movie_id
+            ),
+            FileOperationError::NotOrphan(path) => write!(
+                f,
+                "{} still exists, only movies whose file is gone can be removed",
+                path
+            ),
+            FileOperationError::InvalidToken => write!(f, "unknown or expired confirmation token"),
+        }
+    }
+}
+
+impl std::error::Error for FileOperationError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            FileOperationError::Kodi(err) => Some(err),
+            FileOperationError::Io(err) => Some(err),
+            _ => None,
+        }
+    }
+}
+
+impl From<KodiRpcError> for FileOperationError {
+    fn from(err: KodiRpcError) -> Self {
+        FileOperationError::Kodi(err)
+    }
+}
+
+impl From<std::io::Error> for FileOperationError {
+    fn from(err: std::io::Error) -> Self {
+        FileOperationError::Io(err)
+    }
+}
+
+/// Split a path into its folder, with its trailing separator, and its file name
+fn split_path(path: &str) -> (&str, &str) {
+    let index = path.rfind(&['/', '\\'][..]).map_or(0, |i| i + 1);
+    path.split_at(index)
+}
+
+/// File name following the default movie pattern, "Title Year.ext"
+fn expected_file_name(movie: &Movie, file_name: &str) -> String {
+    let title = movie
+        .title
+        .chars()
+        .filter(|c| !"/\\:*?\"<>|".contains(*c))
+        .collect::<String>();
+    let year = movie.premiered.chars().take(4).collect::<String>();
+    let extension = file_name
+        .rsplit_once('.')
+        .map(|(_, extension)| format!(".{}", extension))
+        .unwrap_or_default();
+    if year.is_empty() {
+        format!("{}{}", title.trim(), extension)
+    } else {
+        format!("{} {}{}", title.trim(), year, extension)
+    }
+}
+
+/// Whether a file is a sidecar of a video named `stem` without its extension: its .nfo, artwork or
+/// subtitles, as named by Kodi
+fn is_sidecar(stem: &str, file_name: &str) -> bool {
+    let rest = match file_name.strip_prefix(stem) {
+        Some(rest) => rest.to_lowercase(),
+        None => return false,
+    };
+    let (middle, extension) = match rest.rsplit_once('.') {
+        Some(split) => split,
+        None => return false,
+    };
+    if middle.is_empty() {
+        extension == "nfo"
+            || IMAGE_EXTENSIONS.contains(&extension)
+            || SUBTITLE_EXTENSIONS.contains(&extension)
+    } else if let Some(kind) = middle.strip_prefix('-') {
+        IMAGE_EXTENSIONS.contains(&extension)
+            && !kind.is_empty()
+            && kind.chars().all(|c| c.is_ascii_alphanumeric())
+    } else if let Some(tags) = middle.strip_prefix('.') {
+        SUBTITLE_EXTENSIONS.contains(&extension)
+            && tags.split('.').all(|tag| {
+                SUBTITLE_FLAGS.contains(&tag)
+                    || ((2..=3).contains(&tag.len())
+                        && tag.chars().all(|c| c.is_ascii_alphabetic()))
+            })
+    } else {
+        false
+    }
+}
+
+/// Sidecars of a local video, with where they go when the video goes to `to`
+fn find_sidecars(from: &str, to: &str) -> std::io::Result<Vec<Sidecar>> {
+    let stem = |path: &str| {
+        let file_name = split_path(path).1;
+        file_name
+            .rsplit_once('.')
+            .map_or(file_name, |(stem, _)| stem)
+            .to_string()
+    };
+    let (folder, video) = split_path(from);
+    let (from_stem, to_stem) = (stem(from), stem(to));
+    let to_folder = split_path(to).0;
+    let mut sidecars = vec![];
+    for entry in std::fs::read_dir(if folder.is_empty() { "." } else { folder })? {
+        let entry = entry?;
+        let file_name = entry.file_name().to_string_lossy().to_string();
+        if file_name != video && entry.file_type()?.is_file() && is_sidecar(&from_stem, &file_name)
+        {
+            sidecars.push(Sidecar {
+                local_from: format!("{}{}", folder, file_name),
+                local_to: format!("{}{}{}", to_folder, to_stem, &file_name[from_stem.len()..]),
+            });
+        }
+    }
+    sidecars.sort_by(|a, b| a.local_from.cmp(&b.local_from));
+    Ok(sidecars)
+}
+
+/// Whether the movie patterns find the title and year of a movie in a new path for its file, as
+/// custom patterns may expect other names than "Title Year.ext"
+fn recognized(movie: &Movie, path: &str, parser: &SourceParsers) -> bool {
+    let comparable = |title: &str| {
+        title
+            .chars()
+            .filter(|c| c.is_alphanumeric())
+            .flat_map(char::to_lowercase)
+            .collect::<String>()
+    };
+    let parsed = parser.parse(path);
+    comparable(&parsed.title) == comparable(&movie.title)
+        && parsed.year == movie.premiered.get(..4).and_then(|year| year.parse().ok())
+}
+
+/// Run file system calls on the thread pool, off the executor
+async fn blocking<T, F>(f: F) -> Result<T, FileOperationError>
+where
+    F: FnOnce() -> Result<T, FileOperationError> + Send + 'static,
+    T: Send + 'static,
+{
+    actix_web::web::block(f).await.map_err(|err| match err {
+        actix_web::error::BlockingError::Error(err) => err,
+        actix_web::error::BlockingError::Canceled => {
+            std::io::Error::other("file operation canceled").into()
+        }
+    })
+}
+
+/// Copy a file to a new file, removing the copy if it fails midway
+fn copy_new(from: &str, to: &str) -> std::io::Result<()> {
+    let mut source = std::fs::File::open(from)?;
+    let mut destination = std::fs::OpenOptions::new()
+        .write(true)
+        .create_new(true)
+        .open(to)?;
+    let copied = std::io::copy(&mut source, &mut destination)
+        .and_then(|_| destination.set_permissions(source.metadata()?.permissions()))
+        .and_then(|_| destination.sync_all());
+    if copied.is_err() {
+        let _ = std::fs::remove_file(to);
+    }
+    copied
+}
+
+/// Move a file without ever replacing another one. The file is linked to its destination, which
+/// fails if something is already there, then unlinked from where it was. When it can't be linked,
+/// across file systems or on shares without links, it is copied to a new file instead.
+fn move_file(from: &str, to: &str) -> Result<(), FileOperationError> {
+    let already_exists = |err: std::io::Error| {
+        if err.kind() == std::io::ErrorKind::AlreadyExists {
+            FileOperationError::AlreadyExists(to.to_string())
+        } else {
+            err.into()
+        }
+    };
+    if let Err(err) = std::fs::hard_link(from, to) {
+        if err.kind() == std::io::ErrorKind::AlreadyExists {
+            return Err(already_exists(err));
+        }
+        event!(
+            Level::DEBUG,
+            "can't link {} to {}, copying it: {}",
+            from,
+            to,
+            err
+        );
+        copy_new(from, to).map_err(already_exists)?;
+    }
+    std::fs::remove_file(from)?;
+    Ok(())
+}
+
+fn local_path(config: &Config, path: &str) -> Result<String, FileOperationError> {
+    config
+        .local_path(path)
+        .ok_or_else(|| FileOperationError::NotMapped(path.to_string()))
+}
+
+/// Preview an action on the file of a movie, nothing is changed. The action is done by
+/// [`execute`] with the token of the plan.
+#[instrument(skip(kodi_rpc, config), err, level = "info")]
+pub async fn prepare(
+    kodi_rpc: &KodiRPC,
+    kodi: &str,
+    movie_id: u16,
+    action: Action,
+    config: &Config,
+) -> Result<Plan, FileOperationError> {
+    let movie = kodi_rpc.get_movie_details(movie_id).await?;
+    let (directory, file_name) = split_path(&movie.path);
+
+    let to = match action {
+        Action::Rename => {
+            let to = format!("{}{}", directory, expected_file_name(&movie, file_name));
+            if to == movie.path {
+                return Err(FileOperationError::NothingToDo(movie.path));
+            }
+            if !recognized(&movie, &to, &hospital::source_parsers(config)?) {
+                return Err(FileOperationError::Unrecognized(to));
+            }
+            Some(to)
+        }
+        Action::Move => {
+            // into the root of the source of the movie, or of the first source
+            let sources = Sources::from_config(config);
+            let root = match sources.find(&movie.path) {
+                Some((source, relative)) => {
+                    if !relative.contains(&['/', '\\'][..]) {
+                        return Err(FileOperationError::NothingToDo(movie.path));
+                    }
+                    source.path.clone()
+                }
+                None => movie_sources(config)
+                    .first()
+                    .map(|source| source.path.clone())
+                    .unwrap_or_default(),
+            };
+            Some(format!("{}{}", root, file_name))
+        }
+        Action::Delete => {
+            let movies = crate::movie_list_cleanup(kodi_rpc.get_all_movies().await?);
+            let redundant = hospital::rank_duplicates(kodi_rpc, &movies, config)
+                .await?
+                .iter()
+                .flat_map(|candidate| candidate.redundant.iter())
+                .any(|copy| copy.movie.id == movie_id);
+            if !redundant {
+                return Err(FileOperationError::NotRedundant(movie_id));
+            }
+            None
+        }
+        Action::Remove => {
+            if hospital::movie_file_exists(kodi_rpc, &movie.path, config).await? {
+                return Err(FileOperationError::NotOrphan(movie.path));
+            }
+            None
+        }
+    };
+
+    // removing a movie from the library doesn't touch any file
+    let mut sidecars = vec![];
+    let (local_from, local_to) = if action == Action::Remove {
+        (None, None)
+    } else {
+        let local_from = local_path(config, &movie.path)?;
+        let local_to = to.as_ref().map(|to| local_path(config, to)).transpose()?;
+        let (from, to) = (local_from.clone(), local_to.clone());
+        sidecars = blocking(move || {
+            std::fs::metadata(&from)
+                .map_err(|err| std::io::Error::new(err.kind(), format!("{}: {}", from, err)))?;
+            let to = match to {
+                Some(to) => to,
+                None => return Ok(vec![]),
+            };
+            let sidecars = find_sidecars(&from, &to)?;
+            match std::iter::once(&to)
+                .chain(sidecars.iter().map(|sidecar| &sidecar.local_to))
+                .find(|to| std::path::Path::new(to).exists())
+            {
+                Some(to) => Err(FileOperationError::AlreadyExists(to.clone())),
+                None => Ok(sidecars),
+            }
+        })
+        .await?;
+        (Some(local_from), local_to)
+    };
+
+    let plan = Plan {
+        kodi: kodi.to_string(),
+        movie_id,
+        action,
+        from: movie.path,
+        to,
+        local_from,
+        local_to,
+        sidecars,
+        token: base64::encode_config(
+            rand::thread_rng().gen::<[u8; 32]>(),
+            base64::URL_SAFE_NO_PAD,
+        ),
+        warnings: vec![],
+    };
+    let now = std::time::Instant::now();
+    let mut pending = PENDING.lock().unwrap();
+    pending.retain(|_, pending| pending.expires > now);
+    pending.insert(
+        plan.token.clone(),
+        Pending {
+            plan: plan.clone(),
+            expires: now + CONFIRMATION_DURATION,
+        },
+    );
+    Ok(plan)
+}
+
+/// Do an action previewed by [`prepare`], then clean the folder the file was in and scan the
+/// folder it is now in so that Kodi follows it. A token can only be used once. Once the action is
+/// done, Kodi failing to clean or scan is reported as a warning of the plan, not as an error.
+#[instrument(skip(kodi_rpc), err, level = "info")]
+pub async fn execute(
+    kodi_rpc: &KodiRPC,
+    kodi: &str,
+    movie_id: u16,
+    action: Action,
+    token: &str,
+) -> Result<Plan, FileOperationError> {
+    let mut plan = PENDING
+        .lock()
+        .unwrap()
+        .remove(token)
+        .filter(|pending| pending.expires > std::time::Instant::now())
+        .map(|pending| pending.plan)
+        .filter(|plan| plan.kodi == kodi && plan.movie_id == movie_id && plan.action == action)
+        .ok_or(FileOperationError::InvalidToken)?;
+
+    match (plan.local_from.clone(), plan.local_to.clone()) {
+        (Some(local_from), Some(local_to)) => {
+            blocking(move || move_file(&local_from, &local_to)).await?;
+            // the video has already moved, a sidecar left behind is only a warning
+            let sidecars = plan.sidecars.clone();
+            let failures = blocking(move || {
+                Ok(sidecars
+                    .iter()
+                    .filter_map(|sidecar| {
+                        move_file(&sidecar.local_from, &sidecar.local_to)
+                            .err()
+                            .map(|err| format!("could not move {}: {}", sidecar.local_from, err))
+                    })
+                    .collect::<Vec<_>>())
+            })
+            .await?;
+            plan.warnings.extend(failures);
+        }
+        (Some(local_from), None) => blocking(move || Ok(std::fs::remove_file(local_from)?)).await?,
+        (None, _) => kodi_rpc.remove_movie(plan.movie_id).await?,
+    }
+    event!(
+        Level::INFO,
+        "{:?} {} done: {} -> {:?}",
+        plan.action,
+        plan.movie_id,
+        plan.from,
+        plan.to
+    );
+
+    // a removed movie is already gone from the library
+    if plan.action != Action::Remove {
+        let folder = split_path(&plan.from).0;
+        if let Err(err) = kodi_rpc.clean_video_directory(folder).await {
+            plan.warnings
+                .push(format!("Kodi could not clean {}: {}", folder, err));
+        }
+    }
+    if let Some(to) = plan.to.as_ref() {
+        let folder = split_path(to).0;
+        if let Err(err) = kodi_rpc.scan_video_directory(folder).await {
+            plan.warnings
+                .push(format!("Kodi could not scan {}: {}", folder, err));
+        }
+    }
+    for warning in plan.warnings.iter() {
+        event!(Level::WARN, "{}", warning);
+    }
+
+    Ok(plan)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn move_file_never_replaces() {
+        let folder = std::env::temp_dir().join(format!("kodi-helper-move-{}", std::process::id()));
+        std::fs::create_dir_all(&folder).unwrap();
+        let path = |name: &str| folder.join(name).to_str().unwrap().to_string();
+        std::fs::write(path("a.mkv"), "a").unwrap();
+        std::fs::write(path("b.mkv"), "b").unwrap();
+
+        assert!(matches!(
+            move_file(&path("a.mkv"), &path("b.mkv")),
+            Err(FileOperationError::AlreadyExists(_))
+        ));
+        assert_eq!(std::fs::read_to_string(path("a.mkv")).unwrap(), "a");
+        assert_eq!(std::fs::read_to_string(path("b.mkv")).unwrap(), "b");
+
+        move_file(&path("a.mkv"), &path("c.mkv")).unwrap();
+        assert!(!std::path::Path::new(&path("a.mkv")).exists());
+        assert_eq!(std::fs::read_to_string(path("c.mkv")).unwrap(), "a");
+
+        std::fs::remove_dir_all(&folder).unwrap();
+    }
+
+    #[test]
+    fn sidecars() {
+        let table = [
+            ("Alien 1979.nfo", true),
+            ("Alien 1979.jpg", true),
+            ("Alien 1979-poster.jpg", true),
+            ("Alien 1979-fanart.PNG", true),
+            ("Alien 1979.srt", true),
+            ("Alien 1979.en.srt", true),
+            ("Alien 1979.eng.forced.srt", true),
+            ("Alien 1979.mkv", false),
+            ("Alien 1979.Directors.Cut.mkv", false),
+            ("Alien 1979.Directors.nfo", false),
+            ("Alien 1979.english.srt", false),
+            ("Alien 1979-poster.nfo", false),
+            ("Alien 1979 2.nfo", false),
+            ("Aliens 1986.nfo", false),
+        ];
+        for (file_name, expected) in table.iter() {
+            assert_eq!(
+                is_sidecar("Alien 1979", file_name),
+                *expected,
+                "{}",
+                file_name
+            );
+        }
+
+        let folder =
+            std::env::temp_dir().join(format!("kodi-helper-sidecars-{}", std::process::id()));
+        std::fs::create_dir_all(folder.join("alien")).unwrap();
+        let path = |name: &str| folder.join(name).to_str().unwrap().to_string();
+        for name in [
+            "alien.mkv",
+            "alien.nfo",
+            "alien-poster.jpg",
+            "alien.en.srt",
+            "other.nfo",
+        ] {
+            std::fs::write(path(&format!("alien/{}", name)), name).unwrap();
+        }
+
+        let sidecars = find_sidecars(&path("alien/alien.mkv"), &path("Alien 1979.mkv")).unwrap();
+        assert_eq!(
+            sidecars
+                .iter()
+                .map(|sidecar| (sidecar.local_from.clone(), sidecar.local_to.clone()))
+                .collect::<Vec<_>>(),
+            vec![
+                (
+                    path("alien/alien-poster.jpg"),
+                    path("Alien 1979-poster.jpg")
+                ),
+                (path("alien/alien.en.srt"), path("Alien 1979.en.srt")),
+                (path("alien/alien.nfo"), path("Alien 1979.nfo")),
+            ]
+        );
+
+        std::fs::remove_dir_all(&folder).unwrap();
+    }
+
+    #[test]
+    fn renamed_file_is_recognized() {
+        let movie = crate::tests::movie(serde_json::json!({
+            "title": "Alien: Covenant",
+            "premiered": "2017-05-19",
+        }));
+        let name = expected_file_name(&movie, "alien.covenant.mkv");
+        assert_eq!(name, "Alien Covenant 2017.mkv");
+
+        let parser = SourceParsers::from_config(&Config::default()).unwrap();
+        assert!(recognized(&movie, &format!("/movies/{}", name), &parser));
+
+        let config = Config {
+            movie_patterns: vec!["^(?P<title>[^ ]+) .*$".to_string()],
+            ..Config::default()
+        };
+        let parser = SourceParsers::from_config(&config).unwrap();
+        assert!(!recognized(&movie, &format!("/movies/{}", name), &parser));
+    }
+}
@@ -0,0 +1,453 @@
+use serde::Serialize;
+
+use crate::sources::{movie_sources, Sources};
+use crate::Config;
+
+/// Patterns tried after the configured ones, on names where dots and underscores are replaced by
+/// spaces and release tags are removed
+const DEFAULT_PATTERNS: &[&str] = &[
+    r"^(?P<title>.+) \(?(?P<year>(?:19|20)[0-9]{2})\)?(?: .*)?$",
+    r"^(?P<title>.+)$",
+];
+
+const RESOLUTIONS: &[&str] = &[
+    "480p", "576p", "720p", "1080p", "1080i", "2160p", "4k", "uhd",
+];
+const SOURCES: &[&str] = &[
+    "bluray", "blu-ray", "bdrip", "brrip", "bdremux", "remux", "web-dl", "webdl", "webrip", "web",
+    "hdtv", "dvdrip", "dvd", "dvdscr", "hdrip", "hdcam", "cam",
+];
+const CODECS: &[&str] = &[
+    "x264", "x265", "h264", "h265", "hevc", "avc", "xvid", "divx", "av1", "vp9",
+];
+const EDITIONS: &[&[&str]] = &[
+    &["extended"],
+    &["unrated"],
+    &["uncut"],
+    &["remastered"],
+    &["theatrical"],
+    &["imax"],
+    &["criterion"],
+    &["directors", "cut"],
+    &["director's", "cut"],
+    &["final", "cut"],
+];
+
+/// Title, year and release tags found in the path of a movie
+#[derive(Serialize, Clone, Debug, Default, PartialEq)]
+pub struct ParsedName {
+    pub title: String,
+    pub year: Option<u16>,
+    pub resolution: Option<String>,
+    pub source: Option<String>,
+    pub codec: Option<String>,
+    pub group: Option<String>,
+    pub edition: Option<String>,
+}
+
+/// Find the title and year of movies from their path, with ordered patterns
+#[derive(Clone, Debug)]
+pub struct FilenameParser {
+    /// Legacy `movie_pattern`, matched on the path relative to the movies directory
+    legacy: Option<regex::Regex>,
+    patterns: Vec<regex::Regex>,
+}
+
+/// Check that a pattern can be used to find the title of movies
+pub fn check_pattern(pattern: &str) -> Result<regex::Regex, String> {
+    let regex = regex::Regex::new(pattern).map_err(|err| err.to_string())?;
+    if regex.capture_names().flatten().any(|name| name == "title") {
+        Ok(regex)
+    } else {
+        Err("a movie pattern needs a `title` group".to_string())
+    }
+}
+
+impl FilenameParser {
+    /// A parser trying `patterns` in order before the default ones. Patterns are matched on the
+    /// name of a file without its extension, and on the names of the folders it is in.
+    pub fn new(legacy: Option<&str>, patterns: &[String]) -> Result<FilenameParser, String> {
+        Ok(FilenameParser {
+            legacy: legacy.map(check_pattern).transpose()?,
+            patterns: patterns
+                .iter()
+                .map(String::as_str)
+                .chain(DEFAULT_PATTERNS.iter().cloned())
+                .map(check_pattern)
+                .collect::<Result<_, _>>()?,
+        })
+    }
+
+    /// Parse the path of a movie in `directory`. The name of the file is used first, then the
+    /// names of the folders it is in for a folder per movie, when the file name has no year.
+    pub fn parse(&self, directory: &str, path: &str) -> ParsedName {
+        let relative = path.strip_prefix(directory).unwrap_or(path);
+
+        if let Some(parsed) = self.legacy.as_ref().and_then(|legacy| {
+            let captures = legacy.captures(relative)?;
+            Some(ParsedName {
+                title: captures.name("title")?.as_str().to_string(),
+                year: captures
+                    .name("year")
+                    .and_then(|year| year.as_str().parse().ok()),
+                ..Default::default()
+            })
+        }) {
+            if parsed.year.is_some() {
+                return parsed;
+            }
+        }
+
+        let mut components = relative
+            .split(&['/', '\\'][..])
+            .filter(|component| !component.is_empty())
+            .rev();
+        let file_name = components.next().unwrap_or(relative);
+        let stem = match file_name.rsplit_once('.') {
+            Some((stem, extension))
+                if !stem.is_empty()
+                    && (1..=4).contains(&extension.len())
+                    && extension.chars().all(|c| c.is_ascii_alphanumeric()) =>
+            {
+                stem
+            }
+            _ => file_name,
+        };
+
+        let from_file = self.parse_name(stem);
+        if from_file.year.is_some() {
+            return from_file;
+        }
+        components
+            .map(|folder| self.parse_name(folder))
+            .find(|parsed| parsed.year.is_some())
+            .map(|parsed| ParsedName {
+                resolution: parsed.resolution.or_else(|| from_file.resolution.clone()),
+                source: parsed.source.or_else(|| from_file.source.clone()),
+                codec: parsed.codec.or_else(|| from_file.codec.clone()),
+                group: parsed.group.or_else(|| from_file.group.clone()),
+                edition: parsed.edition.or_else(|| from_file.edition.clone()),
+                ..parsed
+            })
+            .unwrap_or(from_file)
+    }
+
+    /// Parse a file name without extension or a folder name
+    fn parse_name(&self, name: &str) -> ParsedName {
+        let mut parsed = ParsedName::default();
+        let tokens = name
+            .split(&['.', '_', ' '][..])
+            .filter(|token| !token.is_empty())
+            .collect::<Vec<_>>();
+
+        // words like "web" or "final cut" can be in a title, so when there is a year, only what
+        // follows it is looked at for tags
+        let mut title_end = tokens.len();
+        let mut index = tokens
+            .iter()
+            .skip(1)
+            .position(|token| is_year(token))
+            .map_or(1, |position| position + 2);
+        while index < tokens.len() {
+            let (token, group) = match tokens[index].rsplit_once('-') {
+                Some((tag, group))
+                    if index == tokens.len() - 1
+                        && !is_tag(&tokens[index].to_lowercase())
+                        && is_tag(&tag.to_lowercase())
+                        && !group.is_empty()
+                        && group.chars().all(|c| c.is_ascii_alphanumeric()) =>
+                {
+                    (tag, Some(group))
+                }
+                _ => (tokens[index], None),
+            };
+            let lowercase = token.to_lowercase();
+            let edition = EDITIONS.iter().find(|edition| {
+                edition.len() <= tokens.len() - index
+                    && edition
+                        .iter()
+                        .zip(&tokens[index..])
+                        .all(|(word, token)| token.eq_ignore_ascii_case(word))
+            });
+
+            let found = if RESOLUTIONS.contains(&lowercase.as_str()) {
+                parsed.resolution.get_or_insert(lowercase);
+                1
+            } else if SOURCES.contains(&lowercase.as_str()) {
+                parsed.source.get_or_insert(lowercase);
+                1
+            } else if CODECS.contains(&lowercase.as_str()) {
+                parsed.codec.get_or_insert(lowercase);
+                1
+            } else if let Some(edition) = edition {
+                parsed
+                    .edition
+                    .get_or_insert(tokens[index..index + edition.len()].join(" "));
+                edition.len()
+            } else {
+                0
+            };
+            if let Some(group) = group {
+                parsed.group = Some(group.to_string());
+            }
+            if found > 0 && title_end == tokens.len() {
+                title_end = index;
+            }
+            index += found.max(1);
+        }
+
+        let cleaned = tokens[..title_end].join(" ");
+        if let Some(captures) = self
+            .patterns
+            .iter()
+            .find_map(|pattern| pattern.captures(&cleaned))
+        {
+            parsed.title = captures
+                .name("title")
+                .map(|title| title.as_str().trim().to_string())
+                .unwrap_or_default();
+            parsed.year = captures
+                .name("year")
+                .and_then(|year| year.as_str().parse().ok());
+        }
+        parsed
+    }
+}
+
+/// Parsers of every movie source, trying the patterns of the source of a movie first
+#[derive(Clone, Debug)]
+pub struct SourceParsers {
+    sources: Sources,
+    parsers: std::collections::HashMap<String, FilenameParser>,
+    default: FilenameParser,
+}
+
+impl SourceParsers {
+    /// The parsers of the configured patterns, failing on the first invalid one
+    pub fn from_config(config: &Config) -> Result<SourceParsers, String> {
+        let parser = |source_patterns: &[String]| {
+            let patterns = source_patterns
+                .iter()
+                .chain(config.movie_patterns.iter())
+                .cloned()
+                .collect::<Vec<_>>();
+            FilenameParser::new(config.movie_pattern.as_deref(), &patterns)
+        };
+        Ok(SourceParsers {
+            sources: Sources::from_config(config),
+            parsers: movie_sources(config)
+                .into_iter()
+                .map(|source| Ok((source.path.clone(), parser(&source.movie_patterns)?)))
+                .collect::<Result<_, String>>()?,
+            default: parser(&[])?,
+        })
+    }
+
+    /// Parse the path of a movie, relative to its source
+    pub fn parse(&self, path: &str) -> ParsedName {
+        match self.sources.find(path) {
+            Some((source, relative)) => self.parsers[&source.path].parse("", &relative),
+            None => self.default.parse("", &self.sources.normalize(path)),
+        }
+    }
+}
+
+/// Whether a token is a year, possibly in parentheses or brackets
+fn is_year(token: &str) -> bool {
+    let token = token.trim_matches(&['(', ')', '[', ']'][..]);
+    token.len() == 4
+        && (token.starts_with("19") || token.starts_with("20"))
+        && token.chars().all(|c| c.is_ascii_digit())
+}
+
+fn is_tag(token: &str) -> bool {
+    RESOLUTIONS.contains(&token) || SOURCES.contains(&token) || CODECS.contains(&token)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Path, title, year, and resolution, source, codec, group and edition tags
+    type Case = (
+        &'static str,
+        &'static str,
+        Option<u16>,
+        [Option<&'static str>; 5],
+    );
+
+    fn parse(path: &str) -> ParsedName {
+        FilenameParser::new(None, &[])
+            .unwrap()
+            .parse("/movies/", path)
+    }
+
+    #[test]
+    fn parse_table() {
+        let table: &[Case] = &[
+            ("/movies/Alien 1979.mkv", "Alien", Some(1979), [None; 5]),
+            ("/movies/Alien 1979 (2).avi", "Alien", Some(1979), [None; 5]),
+            ("/movies/Heat (1995).mkv", "Heat", Some(1995), [None; 5]),
+            ("/movies/1917 2019.mkv", "1917", Some(2019), [None; 5]),
+            ("/movies/1917.mkv", "1917", None, [None; 5]),
+            ("/movies/Brazil.mkv", "Brazil", None, [None; 5]),
+            (
+                "/movies/Blade Runner 2049 2017.mkv",
+                "Blade Runner 2049",
+                Some(2017),
+                [None; 5],
+            ),
+            (
+                "/movies/Title.2010.1080p.BluRay.x264-GRP.mkv",
+                "Title",
+                Some(2010),
+                [
+                    Some("1080p"),
+                    Some("bluray"),
+                    Some("x264"),
+                    Some("GRP"),
+                    None,
+                ],
+            ),
+            (
+                "/movies/2001.A.Space.Odyssey.1968.2160p.UHD.BluRay.x265-TERMiNAL.mkv",
+                "2001 A Space Odyssey",
+                Some(1968),
+                [
+                    Some("2160p"),
+                    Some("bluray"),
+                    Some("x265"),
+                    Some("TERMiNAL"),
+                    None,
+                ],
+            ),
+            (
+                "/movies/Some_Movie_2004_Directors_Cut_720p_WEB-DL.mkv",
+                "Some Movie",
+                Some(2004),
+                [
+                    Some("720p"),
+                    Some("web-dl"),
+                    None,
+                    None,
+                    Some("Directors Cut"),
+                ],
+            ),
+            (
+                "/movies/Aliens.1986.EXTENDED.REMASTERED.1080p.BluRay.HEVC.mkv",
+                "Aliens",
+                Some(1986),
+                [
+                    Some("1080p"),
+                    Some("bluray"),
+                    Some("hevc"),
+                    None,
+                    Some("EXTENDED"),
+                ],
+            ),
+            (
+                "/movies/Spider-Man 2002.mkv",
+                "Spider-Man",
+                Some(2002),
+                [None; 5],
+            ),
+            (
+                "/movies/Heat/Heat.1995.1080p.BluRay.x264.mkv",
+                "Heat",
+                Some(1995),
+                [Some("1080p"), Some("bluray"), Some("x264"), None, None],
+            ),
+            (
+                "/movies/Heat (1995)/movie.1080p.mkv",
+                "Heat",
+                Some(1995),
+                [Some("1080p"), None, None, None, None],
+            ),
+            (
+                "/movies/Heat (1995)/Extras/Making of.mkv",
+                "Heat",
+                Some(1995),
+                [None; 5],
+            ),
+            ("/movies/Folder/Untitled.mkv", "Untitled", None, [None; 5]),
+            (
+                "/movies/Charlotte's Web 2006.mkv",
+                "Charlotte's Web",
+                Some(2006),
+                [None; 5],
+            ),
+            (
+                "/movies/The Final Cut 2004.mkv",
+                "The Final Cut",
+                Some(2004),
+                [None; 5],
+            ),
+            (
+                "/movies/Charlotte's.Web.2006.1080p.WEB.x264-GRP.mkv",
+                "Charlotte's Web",
+                Some(2006),
+                [Some("1080p"), Some("web"), Some("x264"), Some("GRP"), None],
+            ),
+            (
+                "/movies/Title.1080p.BluRay.mkv",
+                "Title",
+                None,
+                [Some("1080p"), Some("bluray"), None, None, None],
+            ),
+        ];
+
+        for (path, title, year, [resolution, source, codec, group, edition]) in table {
+            let parsed = parse(path);
+            assert_eq!(parsed.title, *title, "title of {}", path);
+            assert_eq!(parsed.year, *year, "year of {}", path);
+            assert_eq!(
+                parsed.resolution.as_deref(),
+                *resolution,
+                "resolution of {}",
+                path
+            );
+            assert_eq!(parsed.source.as_deref(), *source, "source of {}", path);
+            assert_eq!(parsed.codec.as_deref(), *codec, "codec of {}", path);
+            assert_eq!(parsed.group.as_deref(), *group, "group of {}", path);
+            assert_eq!(parsed.edition.as_deref(), *edition, "edition of {}", path);
+        }
+    }
+
+    #[test]
+    fn directory_with_regex_metacharacters() {
+        let parsed = FilenameParser::new(None, &[])
+            .unwrap()
+            .parse("/films (hd)+/", "/films (hd)+/Alien 1979.mkv");
+        assert_eq!(parsed.title, "Alien");
+        assert_eq!(parsed.year, Some(1979));
+    }
+
+    #[test]
+    fn configured_patterns_come_first() {
+        let parser =
+            FilenameParser::new(None, &[r"^(?P<year>[0-9]{4}) - (?P<title>.+)$".to_string()])
+                .unwrap();
+        let parsed = parser.parse("/movies/", "/movies/1979 - Alien.mkv");
+        assert_eq!(parsed.title, "Alien");
+        assert_eq!(parsed.year, Some(1979));
+    }
+
+    #[test]
+    fn legacy_pattern_on_relative_path() {
+        let parser =
+            FilenameParser::new(Some(r"(?P<title>.+?) \[(?P<year>[0-9]{4})\]\.mkv$"), &[]).unwrap();
+        let parsed = parser.parse("/movies/", "/movies/Alien [1979].mkv");
+        assert_eq!(parsed.title, "Alien");
+        assert_eq!(parsed.year, Some(1979));
+        // without a year from the legacy pattern, the other patterns are used
+        let parsed = parser.parse("/movies/", "/movies/Heat 1995.mkv");
+        assert_eq!(parsed.title, "Heat");
+        assert_eq!(parsed.year, Some(1995));
+    }
+
+    #[test]
+    fn pattern_without_title() {
+        assert!(FilenameParser::new(None, &["(?P<year>[0-9]{4})".to_string()]).is_err());
+        assert!(FilenameParser::new(None, &["(".to_string()]).is_err());
+    }
+}
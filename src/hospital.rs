@@ -0,0 +1,1582 @@
+use serde::{Deserialize, Serialize};
+use tracing::{event, Level};
+
+use crate::filename::SourceParsers;
+use crate::kodi_rpc::{KodiRPC, KodiRpcError};
+use crate::sources::{movie_sources, Sources};
+use crate::{
+    Album, Config, Episode, File, MissingEpisodes, Movie, MovieLists, MusicLibrary, Resolution,
+    Song, TvShow,
+};
+
+pub fn ignored_patterns(config: &Config) -> Vec<regex::Regex> {
+    config
+        .filepatterns_to_ignore
+        .iter()
+        .map(|pattern| regex::Regex::new(pattern).unwrap())
+        .collect()
+}
+
+/// The parsers of the movie patterns, an invalid pattern being reported as a configuration error
+pub fn source_parsers(config: &Config) -> Result<SourceParsers, KodiRpcError> {
+    SourceParsers::from_config(config)
+        .map_err(|err| KodiRpcError::Configuration(format!("invalid movie pattern: {}", err)))
+}
+
+/// Movies with the same title, unless their files have different years
+pub fn find_duplicates(movies: &[Movie], parser: &SourceParsers) -> Vec<Movie> {
+    movies
+        .iter()
+        .map(|movie| (movie.clone(), parser.parse(&movie.path).year))
+        .fold(
+            std::collections::HashMap::new(),
+            |mut map, (movie, year)| {
+                map.entry(movie.title.clone())
+                    .or_insert_with(Vec::new)
+                    .push((movie, year));
+                map
+            },
+        )
+        .iter()
+        .filter(|(_, m_y)| {
+            let years: Vec<Option<u16>> = m_y.iter().map(|(_, y)| *y).collect();
+            years.len() != 1
+                && years.len()
+                    != years
+                        .iter()
+                        .filter_map(|y| *y)
+                        .fold(std::collections::HashSet::new(), |mut set, year| {
+                            set.insert(year);
+                            set
+                        })
+                        .len()
+        })
+        .flat_map(|(_, m_y)| m_y.iter().map(|(m, _)| m.clone()).collect::<Vec<_>>())
+        .collect()
+}
+
+/// Movies with a title or year too different from their file name
+pub fn find_recognition_errors(
+    movies: &[Movie],
+    parser: &SourceParsers,
+    config: &Config,
+) -> Vec<Movie> {
+    movies
+        .iter()
+        .map(|movie| {
+            let parsed = parser.parse(&movie.path);
+            (
+                movie,
+                (
+                    parsed.title,
+                    parsed.year.map(|year| year.to_string()).unwrap_or_default(),
+                ),
+            )
+        })
+        .filter(|(movie, (title, year))| {
+            strsim::levenshtein(&movie.title, title)
+                > config.name_differences_threshold.unwrap_or(3)
+                || !movie.premiered.starts_with(year)
+        })
+        .map(|(movie, _)| movie.clone())
+        .collect()
+}
+
+/// Files that are not ignored and not known by Kodi
+pub fn find_unknown_files(files: Vec<File>, known_paths: &[String], config: &Config) -> Vec<File> {
+    let ignored_patterns = ignored_patterns(config);
+    let known_paths = known_paths.iter().collect::<std::collections::HashSet<_>>();
+
+    files
+        .into_iter()
+        .filter(|f| {
+            !ignored_patterns
+                .iter()
+                .any(|pattern| pattern.is_match(&f.path))
+        })
+        .filter(|f| !known_paths.contains(&f.path))
+        .collect()
+}
+
+/// Files of a folder seen by Kodi down to a depth, read from the local filesystem when the folder
+/// is mapped and readable, listed by Kodi otherwise
+pub async fn list_files(
+    kodi_rpc: &KodiRPC,
+    config: &Config,
+    path: &str,
+    depth: u8,
+) -> Result<Vec<File>, KodiRpcError> {
+    if let Some(local) = config.local_path(path) {
+        let (local_folder, kodi_folder) = (local.clone(), path.to_string());
+        match actix_web::web::block(move || list_local_files(&local_folder, &kodi_folder, depth))
+            .await
+        {
+            Ok(files) => return Ok(files),
+            Err(err) => event!(
+                Level::WARN,
+                "can't read {}, listing {} through Kodi: {}",
+                local,
+                path,
+                err
+            ),
+        }
+    }
+    kodi_rpc.get_files_recursive(path, depth).await
+}
+
+/// Names of the files and folders NAS and operating systems keep their metadata or trash in
+const SYSTEM_ENTRIES: &[&str] = &[
+    "@eaDir",
+    "#recycle",
+    "#snapshot",
+    "$RECYCLE.BIN",
+    "System Volume Information",
+];
+
+/// Whether Kodi skips an entry of a folder: hidden entries, and the metadata and trash of NAS
+fn is_skipped(name: &str) -> bool {
+    name.starts_with('.') || SYSTEM_ENTRIES.contains(&name)
+}
+
+/// Files of a local folder down to a depth, with their paths as seen by Kodi. Only an error on
+/// the folder itself fails, entries and subfolders that can't be read are logged and skipped.
+fn list_local_files(local: &str, kodi: &str, depth: u8) -> std::io::Result<Vec<File>> {
+    let separator = if kodi.ends_with('\\') { '\\' } else { '/' };
+    let with_separator = |path: &str| {
+        if path.ends_with(&['/', '\\'][..]) {
+            path.to_string()
+        } else {
+            format!("{}{}", path, separator)
+        }
+    };
+
+    let mut files = vec![];
+    let mut directories = vec![(std::path::PathBuf::from(local), with_separator(kodi), 0)];
+    while let Some((directory, kodi_directory, level)) = directories.pop() {
+        let entries = match std::fs::read_dir(&directory) {
+            Ok(entries) => entries,
+            Err(err) if level > 0 => {
+                event!(Level::WARN, "skipping {}: {}", directory.display(), err);
+                continue;
+            }
+            Err(err) => return Err(err),
+        };
+        for entry in entries {
+            let entry = match entry {
+                Ok(entry) => entry,
+                Err(err) => {
+                    event!(Level::WARN, "skipping in {}: {}", directory.display(), err);
+                    continue;
+                }
+            };
+            let name = entry.file_name().to_string_lossy().to_string();
+            if is_skipped(&name) {
+                continue;
+            }
+            // follow links like Kodi does, skipping the dangling ones
+            let metadata = match std::fs::metadata(entry.path()) {
+                Ok(metadata) => metadata,
+                Err(err) => {
+                    event!(Level::WARN, "skipping {}: {}", entry.path().display(), err);
+                    continue;
+                }
+            };
+            if metadata.is_dir() {
+                if level < depth {
+                    directories.push((
+                        entry.path(),
+                        format!("{}{}{}", kodi_directory, name, separator),
+                        level + 1,
+                    ));
+                }
+            } else {
+                files.push(File {
+                    path: format!("{}{}", kodi_directory, name),
+                    label: name,
+                });
+            }
+        }
+    }
+    files.sort_by(|a, b| a.path.cmp(&b.path));
+    event!(Level::INFO, "found local files: {}", files.len());
+
+    Ok(files)
+}
+
+/// Files of every movie source, down to the depth of the source, without the ignored ones
+pub async fn list_movie_files(
+    kodi_rpc: &KodiRPC,
+    config: &Config,
+) -> Result<Vec<File>, KodiRpcError> {
+    let ignored_patterns = ignored_patterns(config);
+    let mut files = vec![];
+    for source in movie_sources(config) {
+        let source_ignored_patterns = source
+            .filepatterns_to_ignore
+            .iter()
+            .map(|pattern| regex::Regex::new(pattern).unwrap())
+            .collect::<Vec<_>>();
+        files.extend(
+            list_files(kodi_rpc, config, &source.path, source.depth)
+                .await?
+                .into_iter()
+                .filter(|f| {
+                    !ignored_patterns
+                        .iter()
+                        .chain(source_ignored_patterns.iter())
+                        .any(|pattern| pattern.is_match(&f.path))
+                }),
+        );
+    }
+    Ok(files)
+}
+
+/// Movie files not known by Kodi, paths being compared once normalized
+pub fn find_unknown_movie_files(files: Vec<File>, movies: &[Movie], config: &Config) -> Vec<File> {
+    let sources = Sources::from_config(config);
+    let known_paths = movies
+        .iter()
+        .map(|movie| sources.normalize(&movie.path))
+        .collect::<std::collections::HashSet<_>>();
+
+    files
+        .into_iter()
+        .filter(|f| !known_paths.contains(&sources.normalize(&f.path)))
+        .collect()
+}
+
+/// Files of the movie sources missing from the library
+pub async fn find_missing_movies(
+    kodi_rpc: &KodiRPC,
+    movies: &[Movie],
+    config: &Config,
+) -> Result<Vec<File>, KodiRpcError> {
+    let files = list_movie_files(kodi_rpc, config).await?;
+    Ok(find_unknown_movie_files(files, movies, config))
+}
+
+/// Files checked at the same time when looking for orphans
+const ORPHAN_CHECKS: usize = 8;
+
+/// Whether the file of a movie exists, checked locally when its folder is mapped and mounted, asked
+/// to Kodi otherwise. Every part of a stacked movie must exist.
+pub async fn movie_file_exists(
+    kodi_rpc: &KodiRPC,
+    path: &str,
+    config: &Config,
+) -> Result<bool, KodiRpcError> {
+    let parts = match path.strip_prefix("stack://") {
+        Some(stack) => stack.split(" , ").collect::<Vec<_>>(),
+        None => vec![path],
+    };
+    for part in parts {
+        let local_exists = match config.path_mapping(part) {
+            Some((mapping, local_path)) => {
+                let folder = mapping.local.clone();
+                actix_web::web::block(move || {
+                    // an unmounted folder would make every file look gone
+                    Ok::<_, ()>(if std::path::Path::new(&folder).is_dir() {
+                        Some(std::path::Path::new(&local_path).exists())
+                    } else {
+                        None
+                    })
+                })
+                .await
+                .unwrap_or(None)
+            }
+            None => None,
+        };
+        let exists = match local_exists {
+            Some(exists) => exists,
+            None => kodi_rpc.file_exists(part).await?,
+        };
+        if !exists {
+            return Ok(false);
+        }
+    }
+    Ok(true)
+}
+
+/// Movies of the library whose files no longer exist, checking a few files at a time. Movies that
+/// can't be checked are logged and skipped, it only fails when no movie could be checked.
+pub async fn find_orphans(
+    kodi_rpc: &KodiRPC,
+    movies: &[Movie],
+    config: &Config,
+) -> Result<Vec<Movie>, KodiRpcError> {
+    use futures::StreamExt;
+
+    let checks = futures::stream::iter(movies)
+        .map(|movie| async move {
+            (
+                movie,
+                movie_file_exists(kodi_rpc, &movie.path, config).await,
+            )
+        })
+        .buffered(ORPHAN_CHECKS)
+        .collect::<Vec<_>>()
+        .await;
+
+    let mut orphans = vec![];
+    let mut first_error = None;
+    let mut checked = 0;
+    for (movie, exists) in checks {
+        match exists {
+            Ok(true) => checked += 1,
+            Ok(false) => {
+                checked += 1;
+                orphans.push(movie.clone());
+            }
+            Err(err) => {
+                event!(Level::WARN, "can't check {}: {}", movie.path, err);
+                first_error.get_or_insert(err);
+            }
+        }
+    }
+    match first_error {
+        Some(err) if checked == 0 => Err(err),
+        _ => Ok(orphans),
+    }
+}
+
+/// A copy of a movie, with its file size when Kodi knows it
+#[derive(Serialize, Clone, Debug)]
+pub struct RankedCopy {
+    pub movie: Movie,
+    pub size: Option<u64>,
+    /// Why the kept copy is better, for a redundant copy
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub reason: Option<String>,
+}
+
+/// Copies of a movie ranked by quality, with the one to keep and the redundant ones
+#[derive(Serialize, Clone, Debug)]
+pub struct UpgradeCandidate {
+    pub title: String,
+    /// Year of the files of the copies
+    pub year: Option<u16>,
+    pub keep: RankedCopy,
+    pub redundant: Vec<RankedCopy>,
+}
+
+/// Rank of a video codec, newer codecs giving a better quality for the same size
+fn codec_rank(codec: Option<&String>) -> u8 {
+    match codec.map(|codec| codec.to_lowercase()).as_deref() {
+        Some("av1") => 4,
+        Some("hevc") | Some("h265") | Some("x265") | Some("vp9") => 3,
+        Some("h264") | Some("avc1") | Some("x264") => 2,
+        Some(_) => 1,
+        None => 0,
+    }
+}
+
+/// Compare two copies on resolution, then codec, file size and audio channels, returning the
+/// first criterion on which they differ
+fn compare_copies(a: &RankedCopy, b: &RankedCopy) -> (std::cmp::Ordering, Option<&'static str>) {
+    let criteria = [
+        (
+            a.movie.resolution.cmp(&b.movie.resolution),
+            "lower resolution",
+        ),
+        (
+            codec_rank(a.movie.video_codec.as_ref()).cmp(&codec_rank(b.movie.video_codec.as_ref())),
+            "older codec",
+        ),
+        (a.size.cmp(&b.size), "smaller file"),
+        (
+            a.movie.max_channels().cmp(&b.movie.max_channels()),
+            "fewer audio channels",
+        ),
+    ];
+    criteria
+        .iter()
+        .find(|(ordering, _)| *ordering != std::cmp::Ordering::Equal)
+        .map(|(ordering, reason)| (*ordering, Some(*reason)))
+        .unwrap_or((std::cmp::Ordering::Equal, None))
+}
+
+/// Rank the copies of duplicated movies, `sizes` being the size of their files by path. Copies
+/// are grouped by title and by the year of their file, so that a remake is never ranked against
+/// the original.
+pub fn find_upgrade_candidates(
+    movies: &[Movie],
+    sizes: &std::collections::HashMap<String, u64>,
+    parser: &SourceParsers,
+) -> Vec<UpgradeCandidate> {
+    let mut candidates = find_duplicates(movies, parser)
+        .into_iter()
+        .fold(
+            std::collections::BTreeMap::new(),
+            |mut map: std::collections::BTreeMap<(String, Option<u16>), Vec<RankedCopy>>, movie| {
+                map.entry((movie.title.clone(), parser.parse(&movie.path).year))
+                    .or_default()
+                    .push(RankedCopy {
+                        size: sizes.get(&movie.path).cloned(),
+                        movie,
+                        reason: None,
+                    });
+                map
+            },
+        )
+        .into_iter()
+        .filter_map(|((title, year), mut copies)| {
+            copies.sort_by(|a, b| compare_copies(b, a).0);
+            let mut copies = copies.into_iter();
+            let keep = copies.next()?;
+            let redundant = copies
+                .map(|copy| RankedCopy {
+                    reason: Some(
+                        compare_copies(&copy, &keep)
+                            .1
+                            .unwrap_or("same quality")
+                            .to_string(),
+                    ),
+                    ..copy
+                })
+                .collect::<Vec<_>>();
+            Some(UpgradeCandidate {
+                title,
+                year,
+                keep,
+                redundant,
+            })
+        })
+        .collect::<Vec<_>>();
+    candidates.retain(|candidate| !candidate.redundant.is_empty());
+    candidates
+}
+
+/// Sizes of the files of the movies, by path. They are read locally when the folder is mapped,
+/// in a single call on the thread pool, asked to Kodi otherwise. Files that can't be read are
+/// skipped.
+pub async fn file_sizes(
+    kodi_rpc: &KodiRPC,
+    movies: &[Movie],
+    config: &Config,
+) -> Result<std::collections::HashMap<String, u64>, KodiRpcError> {
+    let local_paths = movies
+        .iter()
+        .filter_map(|movie| Some((movie.path.clone(), config.local_path(&movie.path)?)))
+        .collect::<Vec<_>>();
+    let mut sizes = actix_web::web::block(move || {
+        Ok::<_, ()>(
+            local_paths
+                .into_iter()
+                .filter_map(|(path, local)| Some((path, std::fs::metadata(local).ok()?.len())))
+                .collect::<std::collections::HashMap<_, _>>(),
+        )
+    })
+    .await
+    .unwrap_or_default();
+    for movie in movies {
+        if sizes.contains_key(&movie.path) {
+            continue;
+        }
+        match kodi_rpc.get_file_size(&movie.path).await {
+            Ok(Some(size)) => {
+                sizes.insert(movie.path.clone(), size);
+            }
+            Ok(None) | Err(KodiRpcError::JsonRpc(_)) => (),
+            Err(err) => return Err(err),
+        }
+    }
+    Ok(sizes)
+}
+
+/// Duplicated movies ranked by quality, looking up the size of their files
+pub async fn rank_duplicates(
+    kodi_rpc: &KodiRPC,
+    movies: &[Movie],
+    config: &Config,
+) -> Result<Vec<UpgradeCandidate>, KodiRpcError> {
+    let parser = source_parsers(config)?;
+    let sizes = file_sizes(kodi_rpc, &find_duplicates(movies, &parser), config).await?;
+    Ok(find_upgrade_candidates(movies, &sizes, &parser))
+}
+
+/// How bad an issue found by a check is
+#[derive(Serialize, Clone, Copy, Debug, PartialEq, PartialOrd)]
+#[serde(rename_all = "snake_case")]
+pub enum Severity {
+    Info,
+    Warning,
+    Error,
+}
+
+/// Episodes missing from each season, below the last episode number of the season
+pub fn find_missing_episodes(tvshows: &[TvShow]) -> Vec<MissingEpisodes> {
+    tvshows
+        .iter()
+        .flat_map(|tvshow| {
+            tvshow
+                .seasons
+                .iter()
+                // season 0 holds specials, which are not numbered consistently
+                .filter(|season| season.season != 0)
+                .filter_map(move |season| {
+                    let numbers = season
+                        .episodes
+                        .iter()
+                        .map(|episode| episode.episode)
+                        .collect::<std::collections::HashSet<_>>();
+                    let last = numbers.iter().max().cloned().unwrap_or(0);
+                    let episodes = (1..last)
+                        .filter(|number| !numbers.contains(number))
+                        .collect::<Vec<_>>();
+
+                    if episodes.is_empty() {
+                        None
+                    } else {
+                        Some(MissingEpisodes {
+                            tvshow_id: tvshow.id,
+                            title: tvshow.title.clone(),
+                            season: season.season,
+                            episodes,
+                        })
+                    }
+                })
+        })
+        .collect()
+}
+
+/// Episodes with the same number in the same season of a TV show
+pub fn find_duplicate_episodes(tvshows: &[TvShow]) -> Vec<Episode> {
+    tvshows
+        .iter()
+        .flat_map(|tvshow| tvshow.seasons.iter())
+        .flat_map(|season| season.episodes.iter())
+        .fold(std::collections::HashMap::new(), |mut map, episode| {
+            map.entry((episode.tvshow_id, episode.season, episode.episode))
+                .or_insert_with(Vec::new)
+                .push(episode.clone());
+            map
+        })
+        .into_iter()
+        .filter(|(_, episodes)| episodes.len() > 1)
+        .flat_map(|(_, episodes)| episodes)
+        .collect()
+}
+
+/// Files of the TV shows directory, if there is one
+pub async fn list_episode_files(
+    kodi_rpc: &KodiRPC,
+    config: &Config,
+) -> Result<Vec<File>, KodiRpcError> {
+    match config.tvshows_directory.as_ref() {
+        // TV shows are stored in a folder per show, with an optional folder per season
+        Some(tvshows_directory) => list_files(kodi_rpc, config, tvshows_directory, 2).await,
+        None => Ok(vec![]),
+    }
+}
+
+/// Files of the TV shows directory not known by Kodi
+pub fn find_unknown_episode_files(
+    files: Vec<File>,
+    tvshows: &[TvShow],
+    config: &Config,
+) -> Vec<File> {
+    let known_files = tvshows
+        .iter()
+        .flat_map(|tvshow| tvshow.seasons.iter())
+        .flat_map(|season| season.episodes.iter())
+        .map(|episode| episode.path.clone())
+        .collect::<Vec<_>>();
+    find_unknown_files(files, &known_files, config)
+}
+
+pub fn find_albums_without_art(music_library: &MusicLibrary) -> Vec<Album> {
+    music_library
+        .albums
+        .iter()
+        .filter(|album| album.thumbnail.is_none())
+        .cloned()
+        .collect()
+}
+
+pub fn find_songs_without_album(music_library: &MusicLibrary) -> Vec<Song> {
+    music_library
+        .songs
+        .iter()
+        .filter(|song| song.album_id.is_none())
+        .cloned()
+        .collect()
+}
+
+/// Albums with the same title and artists, ignoring case
+pub fn find_duplicate_albums(music_library: &MusicLibrary) -> Vec<Album> {
+    music_library
+        .albums
+        .iter()
+        .fold(std::collections::HashMap::new(), |mut map, album| {
+            map.entry((
+                album.artists.join(", ").to_lowercase(),
+                album.title.to_lowercase(),
+            ))
+            .or_insert_with(Vec::new)
+            .push(album.clone());
+            map
+        })
+        .into_iter()
+        .filter(|(_, albums)| albums.len() > 1)
+        .flat_map(|(_, albums)| albums)
+        .collect()
+}
+
+/// An issue found by a check, on a movie, on a file unknown by Kodi, or on an item of the TV
+/// shows or of the music library
+#[derive(Serialize, Clone, Debug)]
+pub struct Finding {
+    pub movie: Option<Movie>,
+    /// Empty for items without a file, like albums
+    pub path: String,
+    pub message: String,
+    /// Fields of the movie that are missing or wrong
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub fields: Vec<String>,
+}
+
+impl Finding {
+    fn movie(movie: &Movie, message: String) -> Finding {
+        Finding {
+            movie: Some(movie.clone()),
+            path: movie.path.clone(),
+            message,
+            fields: vec![],
+        }
+    }
+
+    fn other(path: &str, message: String) -> Finding {
+        Finding {
+            movie: None,
+            path: path.to_string(),
+            message,
+            fields: vec![],
+        }
+    }
+
+    /// A finding listing the missing fields of a movie, if any
+    fn missing_fields(movie: &Movie, fields: Vec<&str>) -> Option<Finding> {
+        if fields.is_empty() {
+            None
+        } else {
+            Some(Finding {
+                message: format!("missing {}", fields.join(", ")),
+                fields: fields.into_iter().map(ToString::to_string).collect(),
+                ..Finding::movie(movie, String::new())
+            })
+        }
+    }
+}
+
+/// A check of a library. Checks of the TV shows and of the music library hold them and ignore
+/// the movies.
+pub trait HospitalCheck {
+    /// Identifier of the check, stable across versions
+    fn id(&self) -> &'static str;
+    fn description(&self) -> &'static str;
+    fn severity(&self) -> Severity;
+    fn run(&self, movies: &[Movie], config: &Config) -> Vec<Finding>;
+}
+
+pub struct Duplicates {
+    pub parsers: SourceParsers,
+}
+
+impl HospitalCheck for Duplicates {
+    fn id(&self) -> &'static str {
+        "duplicates"
+    }
+    fn description(&self) -> &'static str {
+        "Movies with the same title"
+    }
+    fn severity(&self) -> Severity {
+        Severity::Warning
+    }
+    fn run(&self, movies: &[Movie], _config: &Config) -> Vec<Finding> {
+        find_duplicates(movies, &self.parsers)
+            .iter()
+            .map(|movie| Finding::movie(movie, format!("{} is duplicated", movie.title)))
+            .collect()
+    }
+}
+
+pub struct RecognitionErrors {
+    pub parsers: SourceParsers,
+}
+
+impl HospitalCheck for RecognitionErrors {
+    fn id(&self) -> &'static str {
+        "recognition"
+    }
+    fn description(&self) -> &'static str {
+        "Movies with a title or year too different from their file name"
+    }
+    fn severity(&self) -> Severity {
+        Severity::Error
+    }
+    fn run(&self, movies: &[Movie], config: &Config) -> Vec<Finding> {
+        find_recognition_errors(movies, &self.parsers, config)
+            .iter()
+            .map(|movie| {
+                Finding::movie(
+                    movie,
+                    format!("recognized as {} ({})", movie.title, movie.premiered),
+                )
+            })
+            .collect()
+    }
+}
+
+pub struct MissingPoster;
+
+impl HospitalCheck for MissingPoster {
+    fn id(&self) -> &'static str {
+        "missing_poster"
+    }
+    fn description(&self) -> &'static str {
+        "Movies without a poster"
+    }
+    fn severity(&self) -> Severity {
+        Severity::Warning
+    }
+    fn run(&self, movies: &[Movie], _config: &Config) -> Vec<Finding> {
+        movies
+            .iter()
+            .filter(|movie| movie.poster.is_none())
+            .map(|movie| Finding::movie(movie, "no poster".to_string()))
+            .collect()
+    }
+}
+
+pub struct MissingResolution;
+
+impl HospitalCheck for MissingResolution {
+    fn id(&self) -> &'static str {
+        "missing_resolution"
+    }
+    fn description(&self) -> &'static str {
+        "Movies without resolution, usually not yet played or with an unreadable file"
+    }
+    fn severity(&self) -> Severity {
+        Severity::Info
+    }
+    fn run(&self, movies: &[Movie], _config: &Config) -> Vec<Finding> {
+        movies
+            .iter()
+            .filter(|movie| movie.resolution.is_none())
+            .map(|movie| Finding::movie(movie, "no resolution".to_string()))
+            .collect()
+    }
+}
+
+pub struct SdMovies;
+
+impl HospitalCheck for SdMovies {
+    fn id(&self) -> &'static str {
+        "sd"
+    }
+    fn description(&self) -> &'static str {
+        "Movies in SD"
+    }
+    fn severity(&self) -> Severity {
+        Severity::Info
+    }
+    fn run(&self, movies: &[Movie], _config: &Config) -> Vec<Finding> {
+        movies
+            .iter()
+            .filter(|movie| movie.resolution == Some(Resolution::Sd))
+            .map(|movie| Finding::movie(movie, "SD resolution".to_string()))
+            .collect()
+    }
+}
+
+pub struct MissingArtwork;
+
+impl HospitalCheck for MissingArtwork {
+    fn id(&self) -> &'static str {
+        "missing_artwork"
+    }
+    fn description(&self) -> &'static str {
+        "Movies without fanart, or in a set without artwork"
+    }
+    fn severity(&self) -> Severity {
+        Severity::Warning
+    }
+    fn run(&self, movies: &[Movie], _config: &Config) -> Vec<Finding> {
+        movies
+            .iter()
+            .filter_map(|movie| {
+                // a missing poster is reported by `MissingPoster`
+                let mut fields = vec![];
+                if movie.fanart.is_none() {
+                    fields.push("fanart");
+                }
+                if movie.set.is_some() {
+                    if movie.set_poster.is_none() {
+                        fields.push("set.poster");
+                    }
+                    if movie.set_fanart.is_none() {
+                        fields.push("set.fanart");
+                    }
+                }
+                Finding::missing_fields(movie, fields)
+            })
+            .collect()
+    }
+}
+
+pub struct IncompleteMetadata;
+
+impl HospitalCheck for IncompleteMetadata {
+    fn id(&self) -> &'static str {
+        "incomplete_metadata"
+    }
+    fn description(&self) -> &'static str {
+        "Movies without plot, genres, cast, rating or premiere date"
+    }
+    fn severity(&self) -> Severity {
+        Severity::Warning
+    }
+    fn run(&self, movies: &[Movie], _config: &Config) -> Vec<Finding> {
+        movies
+            .iter()
+            .filter_map(|movie| {
+                let mut fields = vec![];
+                if movie.plot.trim().is_empty() {
+                    fields.push("plot");
+                }
+                if movie.genres.is_empty() {
+                    fields.push("genres");
+                }
+                if movie.cast.is_empty() {
+                    fields.push("cast");
+                }
+                if movie.rating == 0.0 {
+                    fields.push("rating");
+                }
+                if movie.premiered.is_empty() {
+                    fields.push("premiered");
+                }
+                Finding::missing_fields(movie, fields)
+            })
+            .collect()
+    }
+}
+
+/// Languages a movie must have. A movie matches if it has audio in one of `audio`, and subtitles
+/// in one of `subtitles`, unless it has audio in one of `unless_audio`. Languages can be written
+/// as two or three letter codes.
+#[derive(Deserialize, Serialize, Clone, Debug)]
+pub struct LanguageRule {
+    pub name: String,
+    #[serde(default)]
+    pub audio: Vec<String>,
+    #[serde(default)]
+    pub subtitles: Vec<String>,
+    #[serde(default)]
+    pub unless_audio: Vec<String>,
+}
+
+/// ISO 639-1 codes of common languages, with their ISO 639-2 bibliographic and terminology codes
+/// used by Kodi
+const LANGUAGE_CODES: &[(&str, &str, &str)] = &[
+    ("ar", "ara", "ara"),
+    ("cs", "cze", "ces"),
+    ("da", "dan", "dan"),
+    ("de", "ger", "deu"),
+    ("el", "gre", "ell"),
+    ("en", "eng", "eng"),
+    ("es", "spa", "spa"),
+    ("fi", "fin", "fin"),
+    ("fr", "fre", "fra"),
+    ("he", "heb", "heb"),
+    ("hi", "hin", "hin"),
+    ("hu", "hun", "hun"),
+    ("it", "ita", "ita"),
+    ("ja", "jpn", "jpn"),
+    ("ko", "kor", "kor"),
+    ("nl", "dut", "nld"),
+    ("no", "nor", "nor"),
+    ("pl", "pol", "pol"),
+    ("pt", "por", "por"),
+    ("ru", "rus", "rus"),
+    ("sv", "swe", "swe"),
+    ("tr", "tur", "tur"),
+    ("zh", "chi", "zho"),
+];
+
+/// Two letter code of a language when it is known, the lowercased language otherwise
+fn normalize_language(language: &str) -> String {
+    let language = language.to_lowercase();
+    LANGUAGE_CODES
+        .iter()
+        .find(|(_, bibliographic, terminology)| {
+            language == *bibliographic || language == *terminology
+        })
+        .map(|(code, _, _)| code.to_string())
+        .unwrap_or(language)
+}
+
+fn has_language<'a>(expected: &[String], mut languages: impl Iterator<Item = &'a String>) -> bool {
+    let expected = expected
+        .iter()
+        .map(|language| normalize_language(language))
+        .collect::<Vec<_>>();
+    languages.any(|language| expected.contains(&normalize_language(language)))
+}
+
+impl LanguageRule {
+    /// What the movie is missing to follow this rule
+    fn violations(&self, movie: &Movie) -> Vec<String> {
+        let audio_languages = || {
+            movie
+                .audio
+                .iter()
+                .filter_map(|audio| audio.language.as_ref())
+        };
+        if !self.unless_audio.is_empty() && has_language(&self.unless_audio, audio_languages()) {
+            return vec![];
+        }
+
+        let mut violations = vec![];
+        if !self.audio.is_empty() && !has_language(&self.audio, audio_languages()) {
+            violations.push(format!("no audio in {}", self.audio.join(" or ")));
+        }
+        if !self.subtitles.is_empty()
+            && !has_language(&self.subtitles, movie.subtitle_languages.iter())
+        {
+            violations.push(format!("no subtitles in {}", self.subtitles.join(" or ")));
+        }
+        violations
+    }
+}
+
+/// Movies breaking the configured language rules. Movies without known audio streams are not
+/// checked, as their files have not been read by Kodi yet.
+pub struct LanguageRules;
+
+impl HospitalCheck for LanguageRules {
+    fn id(&self) -> &'static str {
+        "languages"
+    }
+    fn description(&self) -> &'static str {
+        "Movies without audio or subtitles in the expected languages"
+    }
+    fn severity(&self) -> Severity {
+        Severity::Warning
+    }
+    fn run(&self, movies: &[Movie], config: &Config) -> Vec<Finding> {
+        movies
+            .iter()
+            .filter(|movie| !movie.audio.is_empty())
+            .flat_map(|movie| {
+                config.language_rules.iter().filter_map(move |rule| {
+                    let violations = rule.violations(movie);
+                    if violations.is_empty() {
+                        None
+                    } else {
+                        Some(Finding::movie(
+                            movie,
+                            format!("{}: {}", rule.name, violations.join(", ")),
+                        ))
+                    }
+                })
+            })
+            .collect()
+    }
+}
+
+/// Files of the movie sources missing from the library. The files are listed beforehand with
+/// [`list_movie_files`], as checks don't call Kodi.
+pub struct MissingFiles {
+    pub files: Vec<File>,
+}
+
+impl HospitalCheck for MissingFiles {
+    fn id(&self) -> &'static str {
+        "missing_files"
+    }
+    fn description(&self) -> &'static str {
+        "Files of the movie sources missing from the library"
+    }
+    fn severity(&self) -> Severity {
+        Severity::Warning
+    }
+    fn run(&self, movies: &[Movie], config: &Config) -> Vec<Finding> {
+        find_unknown_movie_files(self.files.clone(), movies, config)
+            .into_iter()
+            .map(|file| Finding {
+                movie: None,
+                message: format!("{} is not in the library", file.label),
+                path: file.path,
+                fields: vec![],
+            })
+            .collect()
+    }
+}
+
+/// Movies of an instance that are missing from other instances or differ from them
+pub struct Divergences {
+    pub kodi: String,
+    pub movie_lists: MovieLists,
+}
+
+impl HospitalCheck for Divergences {
+    fn id(&self) -> &'static str {
+        "divergence"
+    }
+    fn description(&self) -> &'static str {
+        "Movies that differ between Kodi instances"
+    }
+    fn severity(&self) -> Severity {
+        Severity::Info
+    }
+    fn run(&self, _movies: &[Movie], config: &Config) -> Vec<Finding> {
+        crate::find_divergences(&self.movie_lists, &config.kodis)
+            .into_iter()
+            .filter_map(|divergence| {
+                let movie = divergence.movies.get(&self.kodi)?;
+                let mut problems = divergence.differences.clone();
+                if !divergence.missing.is_empty() {
+                    problems.push(format!("missing from {}", divergence.missing.join(", ")));
+                }
+                Some(Finding::movie(
+                    movie,
+                    format!("differs: {}", problems.join(", ")),
+                ))
+            })
+            .collect()
+    }
+}
+
+pub struct SeasonGaps {
+    pub tvshows: Vec<TvShow>,
+}
+
+impl HospitalCheck for SeasonGaps {
+    fn id(&self) -> &'static str {
+        "missing_episodes"
+    }
+    fn description(&self) -> &'static str {
+        "Seasons of TV shows with missing episodes"
+    }
+    fn severity(&self) -> Severity {
+        Severity::Info
+    }
+    fn run(&self, _movies: &[Movie], _config: &Config) -> Vec<Finding> {
+        find_missing_episodes(&self.tvshows)
+            .into_iter()
+            .map(|missing| {
+                let path = self
+                    .tvshows
+                    .iter()
+                    .find(|tvshow| tvshow.id == missing.tvshow_id)
+                    .map(|tvshow| tvshow.path.as_str())
+                    .unwrap_or_default();
+                Finding::other(
+                    path,
+                    format!(
+                        "{} season {} is missing episodes {}",
+                        missing.title,
+                        missing.season,
+                        missing
+                            .episodes
+                            .iter()
+                            .map(ToString::to_string)
+                            .collect::<Vec<_>>()
+                            .join(", ")
+                    ),
+                )
+            })
+            .collect()
+    }
+}
+
+pub struct DuplicateEpisodes {
+    pub tvshows: Vec<TvShow>,
+}
+
+impl HospitalCheck for DuplicateEpisodes {
+    fn id(&self) -> &'static str {
+        "duplicate_episodes"
+    }
+    fn description(&self) -> &'static str {
+        "Episodes with the same number in a season"
+    }
+    fn severity(&self) -> Severity {
+        Severity::Warning
+    }
+    fn run(&self, _movies: &[Movie], _config: &Config) -> Vec<Finding> {
+        find_duplicate_episodes(&self.tvshows)
+            .iter()
+            .map(|episode| {
+                Finding::other(
+                    &episode.path,
+                    format!(
+                        "S{:02}E{:02} is duplicated",
+                        episode.season, episode.episode
+                    ),
+                )
+            })
+            .collect()
+    }
+}
+
+/// Files of the TV shows directory missing from the library. The files are listed beforehand
+/// with [`list_episode_files`], as checks don't call Kodi.
+pub struct UnknownEpisodeFiles {
+    pub files: Vec<File>,
+    pub tvshows: Vec<TvShow>,
+}
+
+impl HospitalCheck for UnknownEpisodeFiles {
+    fn id(&self) -> &'static str {
+        "unknown_episode_files"
+    }
+    fn description(&self) -> &'static str {
+        "Files of the TV shows directory missing from the library"
+    }
+    fn severity(&self) -> Severity {
+        Severity::Warning
+    }
+    fn run(&self, _movies: &[Movie], config: &Config) -> Vec<Finding> {
+        find_unknown_episode_files(self.files.clone(), &self.tvshows, config)
+            .into_iter()
+            .map(|file| Finding::other(&file.path, format!("{} is not in the library", file.label)))
+            .collect()
+    }
+}
+
+pub struct AlbumsWithoutArt {
+    pub music_library: MusicLibrary,
+}
+
+impl HospitalCheck for AlbumsWithoutArt {
+    fn id(&self) -> &'static str {
+        "albums_without_art"
+    }
+    fn description(&self) -> &'static str {
+        "Albums without art"
+    }
+    fn severity(&self) -> Severity {
+        Severity::Info
+    }
+    fn run(&self, _movies: &[Movie], _config: &Config) -> Vec<Finding> {
+        find_albums_without_art(&self.music_library)
+            .iter()
+            .map(|album| {
+                Finding::other(
+                    "",
+                    format!("{} by {} has no art", album.title, album.artists.join(", ")),
+                )
+            })
+            .collect()
+    }
+}
+
+pub struct SongsWithoutAlbum {
+    pub music_library: MusicLibrary,
+}
+
+impl HospitalCheck for SongsWithoutAlbum {
+    fn id(&self) -> &'static str {
+        "songs_without_album"
+    }
+    fn description(&self) -> &'static str {
+        "Songs that are not in an album"
+    }
+    fn severity(&self) -> Severity {
+        Severity::Info
+    }
+    fn run(&self, _movies: &[Movie], _config: &Config) -> Vec<Finding> {
+        find_songs_without_album(&self.music_library)
+            .iter()
+            .map(|song| Finding::other(&song.path, format!("{} has no album", song.title)))
+            .collect()
+    }
+}
+
+pub struct DuplicateAlbums {
+    pub music_library: MusicLibrary,
+}
+
+impl HospitalCheck for DuplicateAlbums {
+    fn id(&self) -> &'static str {
+        "duplicate_albums"
+    }
+    fn description(&self) -> &'static str {
+        "Albums with the same title and artists"
+    }
+    fn severity(&self) -> Severity {
+        Severity::Warning
+    }
+    fn run(&self, _movies: &[Movie], _config: &Config) -> Vec<Finding> {
+        find_duplicate_albums(&self.music_library)
+            .iter()
+            .map(|album| {
+                Finding::other(
+                    "",
+                    format!(
+                        "{} by {} is duplicated",
+                        album.title,
+                        album.artists.join(", ")
+                    ),
+                )
+            })
+            .collect()
+    }
+}
+
+/// Result of a check
+#[derive(Serialize, Clone, Debug)]
+pub struct CheckReport {
+    pub id: &'static str,
+    pub description: &'static str,
+    pub severity: Severity,
+    pub count: usize,
+    pub findings: Vec<Finding>,
+}
+
+/// Result of all the checks on a library
+#[derive(Serialize, Clone, Debug)]
+pub struct Report {
+    pub count: usize,
+    pub checks: Vec<CheckReport>,
+}
+
+/// Checks of the TV shows of a library
+pub fn tvshow_checks(tvshows: Vec<TvShow>, files: Vec<File>) -> Vec<Box<dyn HospitalCheck>> {
+    vec![
+        Box::new(SeasonGaps {
+            tvshows: tvshows.clone(),
+        }),
+        Box::new(DuplicateEpisodes {
+            tvshows: tvshows.clone(),
+        }),
+        Box::new(UnknownEpisodeFiles { files, tvshows }),
+    ]
+}
+
+/// Checks of the music library
+pub fn music_checks(music_library: MusicLibrary) -> Vec<Box<dyn HospitalCheck>> {
+    vec![
+        Box::new(AlbumsWithoutArt {
+            music_library: music_library.clone(),
+        }),
+        Box::new(SongsWithoutAlbum {
+            music_library: music_library.clone(),
+        }),
+        Box::new(DuplicateAlbums { music_library }),
+    ]
+}
+
+/// Checks that only need the movies of the library, and the parsers of their names
+pub fn movie_checks(parsers: SourceParsers) -> Vec<Box<dyn HospitalCheck>> {
+    vec![
+        Box::new(Duplicates {
+            parsers: parsers.clone(),
+        }),
+        Box::new(RecognitionErrors { parsers }),
+        Box::new(MissingPoster),
+        Box::new(MissingResolution),
+        Box::new(SdMovies),
+        Box::new(MissingArtwork),
+        Box::new(IncompleteMetadata),
+        Box::new(LanguageRules),
+    ]
+}
+
+/// Run the checks on the movies of a library
+pub fn report(checks: &[Box<dyn HospitalCheck>], movies: &[Movie], config: &Config) -> Report {
+    let checks = checks
+        .iter()
+        .map(|check| {
+            let findings = check.run(movies, config);
+            CheckReport {
+                id: check.id(),
+                description: check.description(),
+                severity: check.severity(),
+                count: findings.len(),
+                findings,
+            }
+        })
+        .collect::<Vec<_>>();
+
+    Report {
+        count: checks.iter().map(|check| check.count).sum(),
+        checks,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tests::movie;
+
+    /// Audio languages, subtitle languages and violations
+    type LanguageCase = (
+        &'static [Option<&'static str>],
+        &'static [&'static str],
+        &'static [&'static str],
+    );
+
+    /// A movie with its audio and subtitle languages, `None` for an audio stream without language
+    fn with_languages(audio: &[Option<&str>], subtitles: &[&str]) -> Movie {
+        movie(serde_json::json!({
+            "audio": audio
+                .iter()
+                .map(|language| serde_json::json!({
+                    "channels": 2,
+                    "codec": "aac",
+                    "language": language,
+                }))
+                .collect::<Vec<_>>(),
+            "subtitle_languages": subtitles,
+        }))
+    }
+
+    #[test]
+    fn normalize_language_table() {
+        let table: &[(&str, &str)] = &[
+            ("fr", "fr"),
+            ("fre", "fr"),
+            ("fra", "fr"),
+            ("FRE", "fr"),
+            ("eng", "en"),
+            ("ger", "de"),
+            ("deu", "de"),
+            ("chi", "zh"),
+            ("zho", "zh"),
+            ("EN", "en"),
+            ("und", "und"),
+            ("Klingon", "klingon"),
+        ];
+        for (language, expected) in table {
+            assert_eq!(normalize_language(language), *expected, "{}", language);
+        }
+    }
+
+    #[test]
+    fn language_rule_table() {
+        // audio in French or English with French subtitles, unless the audio is in French
+        let rule = LanguageRule {
+            name: "french".to_string(),
+            audio: vec!["fr".to_string(), "en".to_string()],
+            subtitles: vec!["fr".to_string()],
+            unless_audio: vec!["fr".to_string()],
+        };
+        const NO_AUDIO: &str = "no audio in fr or en";
+        const NO_SUBTITLES: &str = "no subtitles in fr";
+
+        let table: &[LanguageCase] = &[
+            (&[Some("fre")], &[], &[]),
+            (&[Some("fra")], &[], &[]),
+            (&[Some("eng"), Some("fre")], &[], &[]),
+            (&[Some("eng")], &["fre"], &[]),
+            (&[Some("eng")], &["FRA"], &[]),
+            (&[Some("eng")], &[], &[NO_SUBTITLES]),
+            (&[Some("eng")], &["eng", "ger"], &[NO_SUBTITLES]),
+            (&[Some("ger")], &["fre"], &[NO_AUDIO]),
+            (&[Some("ger")], &[], &[NO_AUDIO, NO_SUBTITLES]),
+            (&[None], &["fre"], &[NO_AUDIO]),
+        ];
+        for (audio, subtitles, expected) in table {
+            assert_eq!(
+                rule.violations(&with_languages(audio, subtitles)),
+                *expected,
+                "audio {:?}, subtitles {:?}",
+                audio,
+                subtitles
+            );
+        }
+    }
+
+    #[test]
+    fn language_rule_without_exception() {
+        let rule = LanguageRule {
+            name: "english".to_string(),
+            audio: vec!["eng".to_string()],
+            subtitles: vec![],
+            unless_audio: vec![],
+        };
+        assert!(rule
+            .violations(&with_languages(&[Some("en")], &[]))
+            .is_empty());
+        assert_eq!(
+            rule.violations(&with_languages(&[Some("fre")], &["eng"])),
+            vec!["no audio in eng"]
+        );
+    }
+
+    fn copy(path: &str, fields: serde_json::Value, size: Option<u64>) -> RankedCopy {
+        let mut fields = fields;
+        fields["path"] = path.into();
+        RankedCopy {
+            movie: movie(fields),
+            size,
+            reason: None,
+        }
+    }
+
+    #[test]
+    fn compare_copies_table() {
+        use std::cmp::Ordering::*;
+
+        let base = || {
+            serde_json::json!({
+                "resolution": "Hd1080p",
+                "video_codec": "h264",
+                "audio": [{ "channels": 6, "codec": "ac3", "language": "eng" }],
+            })
+        };
+        let with = |key: &str, value: serde_json::Value| {
+            let mut fields = base();
+            fields[key] = value;
+            fields
+        };
+        let reference = copy("/movies/a.mkv", base(), Some(1000));
+
+        // the other copy, and how the reference compares to it
+        let table: &[(RankedCopy, (std::cmp::Ordering, Option<&str>))] = &[
+            (copy("/movies/b.mkv", base(), Some(1000)), (Equal, None)),
+            (
+                copy(
+                    "/movies/b.mkv",
+                    with("resolution", "Uhd4k".into()),
+                    Some(10),
+                ),
+                (Less, Some("lower resolution")),
+            ),
+            (
+                copy("/movies/b.mkv", with("resolution", "Sd".into()), Some(5000)),
+                (Greater, Some("lower resolution")),
+            ),
+            (
+                copy(
+                    "/movies/b.mkv",
+                    with("video_codec", "hevc".into()),
+                    Some(10),
+                ),
+                (Less, Some("older codec")),
+            ),
+            (
+                copy(
+                    "/movies/b.mkv",
+                    with("video_codec", "MPEG4".into()),
+                    Some(5000),
+                ),
+                (Greater, Some("older codec")),
+            ),
+            (
+                copy("/movies/b.mkv", base(), Some(2000)),
+                (Less, Some("smaller file")),
+            ),
+            (
+                copy("/movies/b.mkv", base(), None),
+                (Greater, Some("smaller file")),
+            ),
+            (
+                copy(
+                    "/movies/b.mkv",
+                    with(
+                        "audio",
+                        serde_json::json!([{ "channels": 8, "codec": "truehd" }]),
+                    ),
+                    Some(1000),
+                ),
+                (Less, Some("fewer audio channels")),
+            ),
+        ];
+        for (other, expected) in table {
+            assert_eq!(
+                compare_copies(&reference, other),
+                *expected,
+                "{:?}",
+                other.movie
+            );
+        }
+    }
+
+    #[test]
+    fn upgrade_candidates_keep_remakes() {
+        let config = Config::default();
+        let movies = vec![
+            movie(serde_json::json!({
+                "id": 1,
+                "title": "Dune",
+                "path": "/movies/Dune 1984.avi",
+                "resolution": "Sd",
+            })),
+            movie(serde_json::json!({
+                "id": 2,
+                "title": "Dune",
+                "path": "/movies/Dune 1984.mkv",
+                "resolution": "Hd1080p",
+            })),
+            movie(serde_json::json!({
+                "id": 3,
+                "title": "Dune",
+                "path": "/movies/Dune 2021.mkv",
+                "resolution": "Uhd4k",
+            })),
+            movie(serde_json::json!({
+                "id": 4,
+                "title": "Solaris",
+                "path": "/movies/Solaris 1972.mkv",
+            })),
+            movie(serde_json::json!({
+                "id": 5,
+                "title": "Solaris",
+                "path": "/movies/Solaris 2002.mkv",
+            })),
+        ];
+
+        let parsers = SourceParsers::from_config(&config).unwrap();
+        let candidates = find_upgrade_candidates(&movies, &Default::default(), &parsers);
+        assert_eq!(candidates.len(), 1);
+        let candidate = &candidates[0];
+        assert_eq!(candidate.title, "Dune");
+        assert_eq!(candidate.year, Some(1984));
+        assert_eq!(candidate.keep.movie.id, 2);
+        assert_eq!(
+            candidate
+                .redundant
+                .iter()
+                .map(|copy| (copy.movie.id, copy.reason.as_deref()))
+                .collect::<Vec<_>>(),
+            vec![(1, Some("lower resolution"))]
+        );
+    }
+
+    #[test]
+    fn local_files_skip_hidden_and_broken_entries() {
+        let folder = std::env::temp_dir().join(format!("kodi-helper-list-{}", std::process::id()));
+        for subfolder in &["Heat", "@eaDir/Alien 1979.mkv", ".hidden", "Heat/#recycle"] {
+            std::fs::create_dir_all(folder.join(subfolder)).unwrap();
+        }
+        for file in &[
+            "Alien 1979.mkv",
+            ".DS_Store",
+            "Heat/Heat 1995.mkv",
+            "Heat/._Heat 1995.mkv",
+            ".hidden/Brazil 1985.mkv",
+            "Heat/#recycle/Brazil 1985.mkv",
+        ] {
+            std::fs::write(folder.join(file), "").unwrap();
+        }
+        #[cfg(unix)]
+        std::os::unix::fs::symlink(folder.join("gone.mkv"), folder.join("Dangling 2000.mkv"))
+            .unwrap();
+
+        let files = list_local_files(folder.to_str().unwrap(), "smb://nas/movies/", 1).unwrap();
+        assert_eq!(
+            files.iter().map(|f| f.path.as_str()).collect::<Vec<_>>(),
+            vec![
+                "smb://nas/movies/Alien 1979.mkv",
+                "smb://nas/movies/Heat/Heat 1995.mkv",
+            ]
+        );
+
+        std::fs::remove_dir_all(&folder).unwrap();
+    }
+}
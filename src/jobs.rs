@@ -0,0 +1,178 @@
+use futures::future::{AbortHandle, Abortable};
+use lazy_static::lazy_static;
+use serde::{Deserialize, Serialize};
+use tracing::{event, Level};
+
+use crate::events::{Event, EVENTS};
+
+lazy_static! {
+    pub static ref JOBS: Jobs = Jobs::default();
+}
+
+/// Number of finished jobs kept, older ones are forgotten
+const MAX_FINISHED_JOBS: usize = 100;
+
+/// Long-running library operations that can be started as a job
+#[derive(Deserialize, Serialize, Clone, Debug)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum Operation {
+    Clean,
+    Scan,
+    CleanAndScan,
+    RefreshMovieList,
+    RefreshMovies { movie_ids: Vec<u16> },
+}
+
+#[derive(Serialize, Clone, Copy, Debug, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum JobStatus {
+    Running,
+    Succeeded,
+    Failed,
+    Cancelled,
+}
+
+#[derive(Serialize, Clone, Debug)]
+pub struct Job {
+    pub id: u64,
+    pub kodi: String,
+    pub operation: Operation,
+    pub status: JobStatus,
+    /// Between 0 and 1
+    pub progress: f32,
+    pub logs: Vec<String>,
+    pub result: Option<serde_json::Value>,
+    pub error: Option<String>,
+    #[serde(skip)]
+    abort_handle: Option<AbortHandle>,
+}
+
+/// Given to a running job to report what it's doing
+#[derive(Clone, Copy)]
+pub struct JobHandle {
+    id: u64,
+    jobs: &'static Jobs,
+}
+
+impl JobHandle {
+    pub fn log(&self, message: &str) {
+        event!(Level::INFO, "job {}: {}", self.id, message);
+        if let Some(job) = self.jobs.jobs.lock().unwrap().get_mut(&self.id) {
+            job.logs.push(message.to_string());
+        }
+    }
+
+    pub fn progress(&self, progress: f32) {
+        if let Some(job) = self.jobs.jobs.lock().unwrap().get_mut(&self.id) {
+            job.progress = progress.clamp(0.0, 1.0);
+        }
+    }
+}
+
+#[derive(Default)]
+pub struct Jobs {
+    next_id: std::sync::atomic::AtomicU64,
+    jobs: std::sync::Mutex<std::collections::BTreeMap<u64, Job>>,
+}
+
+impl Jobs {
+    /// Start a job in the background, returning it immediately
+    pub fn spawn<F, Fut>(&'static self, kodi: &str, operation: Operation, task: F) -> Job
+    where
+        F: FnOnce(JobHandle) -> Fut,
+        Fut: std::future::Future<Output = Result<serde_json::Value, Box<dyn std::error::Error>>>
+            + 'static,
+    {
+        let id = self
+            .next_id
+            .fetch_add(1, std::sync::atomic::Ordering::SeqCst);
+        let (abort_handle, abort_registration) = AbortHandle::new_pair();
+        let job = Job {
+            id,
+            kodi: kodi.to_string(),
+            operation,
+            status: JobStatus::Running,
+            progress: 0.0,
+            logs: vec![],
+            result: None,
+            error: None,
+            abort_handle: Some(abort_handle),
+        };
+        self.jobs.lock().unwrap().insert(id, job.clone());
+        EVENTS.send(Event::JobStarted {
+            id,
+            kodi: kodi.to_string(),
+        });
+
+        let task = Abortable::new(task(JobHandle { id, jobs: self }), abort_registration);
+        actix_rt::spawn(async move {
+            let outcome = task.await;
+            self.finish(id, outcome);
+        });
+
+        job
+    }
+
+    fn finish(
+        &self,
+        id: u64,
+        outcome: Result<
+            Result<serde_json::Value, Box<dyn std::error::Error>>,
+            futures::future::Aborted,
+        >,
+    ) {
+        let mut jobs = self.jobs.lock().unwrap();
+        if let Some(job) = jobs.get_mut(&id) {
+            job.abort_handle = None;
+            match outcome {
+                Ok(Ok(result)) => {
+                    job.status = JobStatus::Succeeded;
+                    job.progress = 1.0;
+                    job.result = Some(result);
+                }
+                Ok(Err(err)) => {
+                    job.status = JobStatus::Failed;
+                    job.error = Some(err.to_string());
+                }
+                Err(_) => job.status = JobStatus::Cancelled,
+            }
+            event!(Level::INFO, "job {} finished: {:?}", id, job.status);
+            EVENTS.send(Event::JobFinished {
+                id,
+                kodi: job.kodi.clone(),
+                status: job.status,
+            });
+        }
+
+        let finished = jobs
+            .values()
+            .filter(|job| job.status != JobStatus::Running)
+            .map(|job| job.id)
+            .collect::<Vec<_>>();
+        for id in finished
+            .iter()
+            .take(finished.len().saturating_sub(MAX_FINISHED_JOBS))
+        {
+            jobs.remove(id);
+        }
+    }
+
+    pub fn get(&self, id: u64) -> Option<Job> {
+        self.jobs.lock().unwrap().get(&id).cloned()
+    }
+
+    pub fn list(&self) -> Vec<Job> {
+        self.jobs.lock().unwrap().values().cloned().collect()
+    }
+
+    /// Cancel a running job. The operation already sent to Kodi, like a scan, will still finish
+    /// on Kodi's side.
+    pub fn cancel(&self, id: u64) -> Option<Job> {
+        let jobs = self.jobs.lock().unwrap();
+        let job = jobs.get(&id)?;
+        if let Some(abort_handle) = job.abort_handle.as_ref() {
+            abort_handle.abort();
+        }
+        Some(job.clone())
+    }
+}
@@ -1,20 +1,55 @@
+use serde::Serialize;
 use tracing::{event, instrument, Level};
 
 use super::*;
 
+const CLEAN_TIMEOUT: std::time::Duration = std::time::Duration::from_secs(30 * 60);
+
+#[derive(Serialize, Clone, Debug)]
+struct JsonRPCCleanDirectoryRequestParams {
+    directory: String,
+    showdialogs: bool,
+}
+
 impl KodiRPC {
     #[instrument(err, level = "info")]
-    pub async fn clean_video_library(&self) -> Result<(), Box<dyn std::error::Error>> {
+    pub async fn clean_video_library(&self) -> Result<(), KodiRpcError> {
         event!(Level::TRACE, "Preparing RPC request");
-        self.send_rpc_request::<(), String>(&JsonRPCRequest {
-            jsonrpc: "2.0".to_string(),
-            id: 1,
-            method: "VideoLibrary.Clean".to_string(),
-            params: None,
-        })
+        // Kodi only answers once the clean is done
+        self.send_rpc_request_with_timeout::<(), String>(
+            &JsonRPCRequest {
+                jsonrpc: "2.0".to_string(),
+                id: 1,
+                method: "VideoLibrary.Clean".to_string(),
+                params: None,
+            },
+            CLEAN_TIMEOUT,
+        )
         .await?;
         event!(Level::INFO, "Cleaned Kodi Library");
 
         Ok(())
     }
+
+    /// Clean only a directory, removing the movies whose files are gone from it
+    #[instrument(err, level = "info")]
+    pub async fn clean_video_directory(&self, directory: &str) -> Result<(), KodiRpcError> {
+        event!(Level::TRACE, "Preparing RPC request");
+        self.send_rpc_request_with_timeout::<JsonRPCCleanDirectoryRequestParams, String>(
+            &JsonRPCRequest {
+                jsonrpc: "2.0".to_string(),
+                id: 1,
+                method: "VideoLibrary.Clean".to_string(),
+                params: Some(JsonRPCCleanDirectoryRequestParams {
+                    directory: directory.to_string(),
+                    showdialogs: false,
+                }),
+            },
+            CLEAN_TIMEOUT,
+        )
+        .await?;
+        event!(Level::INFO, "Cleaned {}", directory);
+
+        Ok(())
+    }
 }
@@ -0,0 +1,131 @@
+use serde::{Deserialize, Serialize};
+
+#[derive(Deserialize, Serialize, Clone, Debug)]
+pub struct JsonRPCErrorData {
+    #[serde(default)]
+    pub message: String,
+    pub method: String,
+}
+
+/// Error returned by Kodi in a JSON-RPC response
+#[derive(Deserialize, Serialize, Clone, Debug)]
+pub struct JsonRPCError {
+    pub code: i32,
+    pub message: String,
+    pub data: Option<JsonRPCErrorData>,
+}
+impl std::fmt::Display for JsonRPCError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if let Some(ref data) = self.data {
+            write!(
+                f,
+                "{} when calling {}: {}",
+                self.message, data.method, data.message
+            )
+        } else {
+            write!(f, "{} ", self.message)
+        }
+    }
+}
+impl std::error::Error for JsonRPCError {}
+
+/// Error when talking to Kodi
+#[derive(Debug)]
+pub enum KodiRpcError {
+    /// The request could not be sent or the response could not be read
+    Transport(reqwest::Error),
+    /// Kodi did not answer in time
+    Timeout,
+    /// Kodi refused the request credentials
+    Unauthorized,
+    /// Kodi answered with an unexpected HTTP status
+    HttpStatus(u16),
+    /// Kodi answered with a JSON-RPC error
+    JsonRpc(JsonRPCError),
+    /// The response from Kodi could not be understood
+    Deserialization { field: String, message: String },
+    /// The Kodi url is not valid
+    InvalidUrl(String),
+    /// The Kodi instance is badly configured
+    Configuration(String),
+    /// The TCP connection to Kodi failed
+    Io(std::io::Error),
+}
+
+impl KodiRpcError {
+    /// Short name of the kind of error
+    pub fn kind(&self) -> &'static str {
+        match self {
+            KodiRpcError::Transport(_) => "transport",
+            KodiRpcError::Timeout => "timeout",
+            KodiRpcError::Unauthorized => "unauthorized",
+            KodiRpcError::HttpStatus(_) => "http_status",
+            KodiRpcError::JsonRpc(_) => "json_rpc",
+            KodiRpcError::Deserialization { .. } => "deserialization",
+            KodiRpcError::InvalidUrl(_) => "invalid_url",
+            KodiRpcError::Configuration(_) => "configuration",
+            KodiRpcError::Io(_) => "io",
+        }
+    }
+}
+
+impl std::fmt::Display for KodiRpcError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            KodiRpcError::Transport(err) => write!(f, "error talking to Kodi: {}", err),
+            KodiRpcError::Timeout => write!(f, "Kodi did not answer in time"),
+            KodiRpcError::Unauthorized => write!(f, "Kodi refused the credentials"),
+            KodiRpcError::HttpStatus(status) => {
+                write!(f, "Kodi answered with HTTP status {}", status)
+            }
+            KodiRpcError::JsonRpc(err) => write!(f, "Kodi returned an error: {}", err),
+            KodiRpcError::Deserialization { field, message } => write!(
+                f,
+                "invalid response from Kodi for field '{}': {}",
+                field, message
+            ),
+            KodiRpcError::InvalidUrl(url) => write!(f, "invalid Kodi url: {}", url),
+            KodiRpcError::Configuration(message) => {
+                write!(f, "invalid Kodi configuration: {}", message)
+            }
+            KodiRpcError::Io(err) => write!(f, "error connecting to Kodi: {}", err),
+        }
+    }
+}
+
+impl std::error::Error for KodiRpcError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            KodiRpcError::Transport(err) => Some(err),
+            KodiRpcError::JsonRpc(err) => Some(err),
+            KodiRpcError::Io(err) => Some(err),
+            _ => None,
+        }
+    }
+}
+
+impl From<reqwest::Error> for KodiRpcError {
+    fn from(err: reqwest::Error) -> Self {
+        if err.is_timeout() {
+            KodiRpcError::Timeout
+        } else {
+            KodiRpcError::Transport(err)
+        }
+    }
+}
+
+impl From<std::io::Error> for KodiRpcError {
+    fn from(err: std::io::Error) -> Self {
+        if err.kind() == std::io::ErrorKind::TimedOut {
+            KodiRpcError::Timeout
+        } else {
+            KodiRpcError::Io(err)
+        }
+    }
+}
+
+impl From<JsonRPCError> for KodiRpcError {
+    fn from(err: JsonRPCError) -> Self {
+        KodiRpcError::JsonRpc(err)
+    }
+}
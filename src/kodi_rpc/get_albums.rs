@@ -0,0 +1,74 @@
+use serde::{Deserialize, Serialize};
+use tracing::{event, instrument, Level};
+
+use super::*;
+
+#[derive(Serialize, Clone, Debug)]
+struct JsonRPCGetAlbumsRequestParams {
+    properties: Vec<String>,
+}
+
+#[derive(Deserialize, Clone, Debug)]
+#[serde(deny_unknown_fields)]
+struct AlbumDetailsResponse {
+    #[allow(dead_code)]
+    label: String,
+    albumid: u32,
+    title: String,
+    artist: Vec<String>,
+    artistid: Vec<u32>,
+    year: u16,
+    genre: Vec<String>,
+    thumbnail: String,
+    playcount: u16,
+}
+
+#[derive(Deserialize, Clone, Debug)]
+struct AlbumsResponse {
+    #[serde(default)]
+    albums: Vec<AlbumDetailsResponse>,
+}
+
+impl KodiRPC {
+    #[instrument(err, level = "info")]
+    pub async fn get_all_albums(&self) -> Result<Vec<crate::Album>, KodiRpcError> {
+        event!(Level::TRACE, "Preparing RPC request");
+        let data = self
+            .send_rpc_request::<JsonRPCGetAlbumsRequestParams, AlbumsResponse>(&JsonRPCRequest {
+                jsonrpc: "2.0".to_string(),
+                id: 1,
+                method: "AudioLibrary.GetAlbums".to_string(),
+                params: Some(JsonRPCGetAlbumsRequestParams {
+                    properties: vec![
+                        "title".to_string(),
+                        "artist".to_string(),
+                        "artistid".to_string(),
+                        "year".to_string(),
+                        "genre".to_string(),
+                        "thumbnail".to_string(),
+                        "playcount".to_string(),
+                    ],
+                }),
+            })
+            .await?;
+        event!(Level::INFO, "found albums: {}", data.albums.len());
+
+        let mut albums: Vec<crate::Album> = data
+            .albums
+            .into_iter()
+            .map(|album| crate::Album {
+                id: album.albumid,
+                title: album.title,
+                artists: album.artist,
+                artist_ids: album.artistid,
+                year: album.year,
+                genres: album.genre,
+                thumbnail: non_empty_image_url(album.thumbnail),
+                playcount: album.playcount,
+            })
+            .collect();
+
+        albums.sort_by(|a, b| a.title.cmp(&b.title));
+        Ok(albums)
+    }
+}
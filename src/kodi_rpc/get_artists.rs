@@ -0,0 +1,64 @@
+use serde::{Deserialize, Serialize};
+use tracing::{event, instrument, Level};
+
+use super::*;
+
+#[derive(Serialize, Clone, Debug)]
+struct JsonRPCGetArtistsRequestParams {
+    properties: Vec<String>,
+}
+
+#[derive(Deserialize, Clone, Debug)]
+#[serde(deny_unknown_fields)]
+struct ArtistDetailsResponse {
+    #[allow(dead_code)]
+    label: String,
+    artistid: u32,
+    artist: String,
+    genre: Vec<String>,
+    thumbnail: String,
+    #[allow(dead_code)]
+    fanart: String,
+}
+
+#[derive(Deserialize, Clone, Debug)]
+struct ArtistsResponse {
+    #[serde(default)]
+    artists: Vec<ArtistDetailsResponse>,
+}
+
+impl KodiRPC {
+    #[instrument(err, level = "info")]
+    pub async fn get_all_artists(&self) -> Result<Vec<crate::Artist>, KodiRpcError> {
+        event!(Level::TRACE, "Preparing RPC request");
+        let data = self
+            .send_rpc_request::<JsonRPCGetArtistsRequestParams, ArtistsResponse>(&JsonRPCRequest {
+                jsonrpc: "2.0".to_string(),
+                id: 1,
+                method: "AudioLibrary.GetArtists".to_string(),
+                params: Some(JsonRPCGetArtistsRequestParams {
+                    properties: vec![
+                        "genre".to_string(),
+                        "thumbnail".to_string(),
+                        "fanart".to_string(),
+                    ],
+                }),
+            })
+            .await?;
+        event!(Level::INFO, "found artists: {}", data.artists.len());
+
+        let mut artists: Vec<crate::Artist> = data
+            .artists
+            .into_iter()
+            .map(|artist| crate::Artist {
+                id: artist.artistid,
+                name: artist.artist,
+                genres: artist.genre,
+                thumbnail: non_empty_image_url(artist.thumbnail),
+            })
+            .collect();
+
+        artists.sort_by(|a, b| a.name.cmp(&b.name));
+        Ok(artists)
+    }
+}
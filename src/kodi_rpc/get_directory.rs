@@ -13,8 +13,6 @@ struct File {
     file: String,
     filetype: String,
     label: String,
-    #[serde(rename = "type")]
-    ty: String,
 }
 
 #[derive(Deserialize, Clone, Debug)]
@@ -23,11 +21,7 @@ struct DirectoryResponse {
 }
 
 impl KodiRPC {
-    #[instrument(err, level = "info")]
-    pub async fn get_directory(
-        &self,
-        path: &str,
-    ) -> Result<Vec<crate::File>, Box<dyn std::error::Error>> {
+    async fn list_directory(&self, path: &str) -> Result<Vec<File>, KodiRpcError> {
         event!(Level::TRACE, "Preparing RPC request");
         let data = self
             .send_rpc_request::<JsonRPCGetDirectoryRequestParams, DirectoryResponse>(
@@ -43,8 +37,14 @@ impl KodiRPC {
             .await?;
         event!(Level::INFO, "found files: {}", data.files.len());
 
-        let files = data
-            .files
+        Ok(data.files)
+    }
+
+    #[instrument(err, level = "info")]
+    pub async fn get_directory(&self, path: &str) -> Result<Vec<crate::File>, KodiRpcError> {
+        let files = self
+            .list_directory(path)
+            .await?
             .into_iter()
             .map(|f| crate::File {
                 path: f.file,
@@ -54,4 +54,33 @@ impl KodiRPC {
 
         Ok(files)
     }
+
+    /// List files in a directory and its subdirectories, up to `depth` levels deep
+    #[instrument(err, level = "info")]
+    pub async fn get_files_recursive(
+        &self,
+        path: &str,
+        depth: u8,
+    ) -> Result<Vec<crate::File>, KodiRpcError> {
+        let mut files = vec![];
+        let mut directories = vec![(path.to_string(), 0)];
+
+        while let Some((directory, level)) = directories.pop() {
+            for f in self.list_directory(&directory).await? {
+                if f.filetype == "directory" {
+                    if level < depth {
+                        directories.push((f.file, level + 1));
+                    }
+                } else {
+                    files.push(crate::File {
+                        path: f.file,
+                        label: f.label,
+                    });
+                }
+            }
+        }
+        event!(Level::INFO, "found files recursively: {}", files.len());
+
+        Ok(files)
+    }
 }
@@ -0,0 +1,89 @@
+use serde::{Deserialize, Serialize};
+use tracing::{event, instrument, Level};
+
+use super::*;
+
+#[derive(Serialize, Clone, Debug)]
+struct JsonRPCGetEpisodesRequestParams {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    tvshowid: Option<u16>,
+    properties: Vec<String>,
+}
+
+#[derive(Deserialize, Clone, Debug)]
+#[serde(deny_unknown_fields)]
+struct EpisodeDetailsResponse {
+    #[allow(dead_code)]
+    label: String,
+    episodeid: u32,
+    tvshowid: u16,
+    season: u16,
+    episode: u16,
+    title: String,
+    file: String,
+    firstaired: String,
+    runtime: u16,
+    rating: f32,
+    playcount: u8,
+}
+
+#[derive(Deserialize, Clone, Debug)]
+struct EpisodesResponse {
+    #[serde(default)]
+    episodes: Vec<EpisodeDetailsResponse>,
+}
+
+impl KodiRPC {
+    /// Get the episodes of a TV show, or of all TV shows
+    #[instrument(err, level = "info")]
+    pub async fn get_episodes(
+        &self,
+        tvshow_id: Option<u16>,
+    ) -> Result<Vec<crate::Episode>, KodiRpcError> {
+        event!(Level::TRACE, "Preparing RPC request");
+        let data = self
+            .send_rpc_request::<JsonRPCGetEpisodesRequestParams, EpisodesResponse>(
+                &JsonRPCRequest {
+                    jsonrpc: "2.0".to_string(),
+                    id: 1,
+                    method: "VideoLibrary.GetEpisodes".to_string(),
+                    params: Some(JsonRPCGetEpisodesRequestParams {
+                        tvshowid: tvshow_id,
+                        properties: vec![
+                            "tvshowid".to_string(),
+                            "season".to_string(),
+                            "episode".to_string(),
+                            "title".to_string(),
+                            "file".to_string(),
+                            "firstaired".to_string(),
+                            "runtime".to_string(),
+                            "rating".to_string(),
+                            "playcount".to_string(),
+                        ],
+                    }),
+                },
+            )
+            .await?;
+        event!(Level::INFO, "found episodes: {}", data.episodes.len());
+
+        let mut episodes: Vec<crate::Episode> = data
+            .episodes
+            .into_iter()
+            .map(|episode| crate::Episode {
+                id: episode.episodeid,
+                tvshow_id: episode.tvshowid,
+                season: episode.season,
+                episode: episode.episode,
+                title: episode.title,
+                path: episode.file,
+                firstaired: episode.firstaired,
+                runtime: episode.runtime,
+                rating: episode.rating,
+                playcount: episode.playcount,
+            })
+            .collect();
+
+        episodes.sort_by_key(|episode| (episode.tvshow_id, episode.season, episode.episode));
+        Ok(episodes)
+    }
+}
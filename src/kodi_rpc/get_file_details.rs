@@ -0,0 +1,116 @@
+use serde::{Deserialize, Serialize};
+use tracing::{event, instrument, Level};
+
+use super::*;
+
+#[derive(Serialize, Clone, Debug)]
+struct JsonRPCGetFileDetailsRequestParams {
+    file: String,
+    properties: Vec<String>,
+}
+
+#[derive(Deserialize, Clone, Debug)]
+struct FileDetails {
+    size: Option<u64>,
+}
+
+#[derive(Deserialize, Clone, Debug)]
+struct FileDetailsResponse {
+    filedetails: FileDetails,
+}
+
+impl KodiRPC {
+    /// Size of a file in bytes, `None` if Kodi doesn't know it
+    #[instrument(err, level = "info")]
+    pub async fn get_file_size(&self, path: &str) -> Result<Option<u64>, KodiRpcError> {
+        event!(Level::TRACE, "Preparing RPC request");
+        let data = self
+            .send_rpc_request::<JsonRPCGetFileDetailsRequestParams, FileDetailsResponse>(
+                &JsonRPCRequest {
+                    jsonrpc: "2.0".to_string(),
+                    id: 1,
+                    method: "Files.GetFileDetails".to_string(),
+                    params: Some(JsonRPCGetFileDetailsRequestParams {
+                        file: path.to_string(),
+                        properties: vec!["size".to_string()],
+                    }),
+                },
+            )
+            .await?;
+
+        Ok(data.filedetails.size)
+    }
+
+    /// Whether a file exists. Kodi answers with an error for the files it can't find, but also
+    /// when their share can't be reached, so a file is only gone if a folder above it on its share
+    /// can be listed.
+    #[instrument(err, level = "info")]
+    pub async fn file_exists(&self, path: &str) -> Result<bool, KodiRpcError> {
+        event!(Level::TRACE, "Preparing RPC request");
+        match self
+            .send_rpc_request::<JsonRPCGetFileDetailsRequestParams, FileDetailsResponse>(
+                &JsonRPCRequest {
+                    jsonrpc: "2.0".to_string(),
+                    id: 1,
+                    method: "Files.GetFileDetails".to_string(),
+                    params: Some(JsonRPCGetFileDetailsRequestParams {
+                        file: path.to_string(),
+                        properties: vec![],
+                    }),
+                },
+            )
+            .await
+        {
+            Ok(_) => Ok(true),
+            Err(KodiRpcError::JsonRpc(err)) => {
+                let mut folder = path;
+                while let Some(parent) = parent_folder(folder) {
+                    match self.get_directory(parent).await {
+                        Ok(_) => return Ok(false),
+                        Err(KodiRpcError::JsonRpc(_)) => folder = parent,
+                        Err(err) => return Err(err),
+                    }
+                }
+                Err(KodiRpcError::JsonRpc(err))
+            }
+            Err(err) => Err(err),
+        }
+    }
+}
+
+/// Folder above a path, with its trailing separator, up to the root of its share
+fn parent_folder(path: &str) -> Option<&str> {
+    let index = path
+        .trim_end_matches(&['/', '\\'][..])
+        .rfind(&['/', '\\'][..])?;
+    let parent = &path[..=index];
+    if parent.ends_with("://") {
+        None
+    } else {
+        Some(parent)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parent_folders() {
+        let table: &[(&str, Option<&str>)] = &[
+            ("/movies/Heat/Heat 1995.mkv", Some("/movies/Heat/")),
+            ("/movies/Heat/", Some("/movies/")),
+            ("/movies/", Some("/")),
+            ("/", None),
+            ("smb://nas/movies/Heat 1995.mkv", Some("smb://nas/movies/")),
+            ("smb://nas/movies/", Some("smb://nas/")),
+            ("smb://nas/", None),
+            ("C:\\Movies\\Heat 1995.mkv", Some("C:\\Movies\\")),
+            ("C:\\Movies\\", Some("C:\\")),
+            ("C:\\", None),
+        ];
+        for (path, parent) in table {
+            assert_eq!(parent_folder(path), *parent, "{}", path);
+        }
+    }
+}
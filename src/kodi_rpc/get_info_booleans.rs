@@ -19,7 +19,7 @@ impl KodiRPC {
     pub async fn get_info_boolean(
         &self,
         info_booleans: Vec<String>,
-    ) -> Result<std::collections::HashMap<String, bool>, Box<dyn std::error::Error>> {
+    ) -> Result<std::collections::HashMap<String, bool>, KodiRpcError> {
         event!(Level::TRACE, "Preparing RPC request to get info boolean");
         let data = self
             .send_rpc_request::<JsonRPCGetInfoBooleansRequestParams, InfoBooleansResponse>(
@@ -35,11 +35,10 @@ impl KodiRPC {
             .await?;
 
         let mut result = std::collections::HashMap::new();
-        info_booleans.into_iter().for_each(|b| match b.as_ref() {
-            "Library.IsScanningVideo" => {
+        info_booleans.into_iter().for_each(|b| {
+            if b == "Library.IsScanningVideo" {
                 result.insert(b, data.library_is_scanning_video);
             }
-            _ => (),
         });
         Ok(result)
     }
@@ -3,11 +3,6 @@ use tracing::{event, instrument, Level};
 
 use super::*;
 
-#[derive(Serialize, Clone, Debug)]
-struct JsonRPCRequestLimits {
-    end: u16,
-}
-
 #[derive(Serialize, Clone, Debug)]
 struct JsonRPCGetMoviesRequestParams {
     properties: Vec<String>,
@@ -17,7 +12,9 @@ struct JsonRPCGetMoviesRequestParams {
 #[derive(Deserialize, Clone, Debug)]
 #[serde(deny_unknown_fields)]
 struct MoviesArtResponse {
+    #[allow(dead_code)]
     icon: Option<String>,
+    #[allow(dead_code)]
     thumb: Option<String>,
     fanart: Option<String>,
     poster: Option<String>,
@@ -26,6 +23,7 @@ struct MoviesArtResponse {
     #[serde(rename = "set.poster")]
     set_poster: Option<String>,
     #[serde(rename = "set.thumb")]
+    #[allow(dead_code)]
     set_thumb: Option<String>,
 }
 
@@ -46,11 +44,17 @@ struct MoviesSubtitleStreamDetailsResponse {
 struct MoviesVideoStreamDetailsResponse {
     aspect: f32,
     codec: String,
+    #[allow(dead_code)]
     duration: u16,
     height: u16,
+    #[allow(dead_code)]
     width: u16,
+    #[allow(dead_code)]
     language: String,
     stereomode: String,
+    /// Only sent by Kodi 19 and later
+    #[serde(default)]
+    hdrtype: String,
 }
 
 #[derive(Deserialize, Clone, Debug)]
@@ -65,6 +69,7 @@ struct MoviesStreamDetailsResponse {
 #[serde(deny_unknown_fields)]
 struct MovieDetailsResponse {
     art: MoviesArtResponse,
+    #[allow(dead_code)]
     label: String,
     movieid: u16,
     runtime: u16,
@@ -79,12 +84,14 @@ struct MovieDetailsResponse {
     genre: Vec<String>,
     streamdetails: MoviesStreamDetailsResponse,
     cast: Vec<CastMemberResponse>,
+    plot: String,
 }
 
 #[derive(Deserialize, Clone, Debug)]
 #[serde(deny_unknown_fields)]
 struct CastMemberResponse {
     name: String,
+    #[allow(dead_code)]
     order: u16,
     role: String,
     thumbnail: Option<String>,
@@ -95,9 +102,119 @@ struct MoviesResponse {
     movies: Vec<MovieDetailsResponse>,
 }
 
+#[derive(Serialize, Clone, Debug)]
+struct JsonRPCGetMovieDetailsRequestParams {
+    movieid: u16,
+    properties: Vec<String>,
+}
+
+#[derive(Deserialize, Clone, Debug)]
+struct MovieResponse {
+    moviedetails: MovieDetailsResponse,
+}
+
+fn movie_properties() -> Vec<String> {
+    vec![
+        "art".to_string(),
+        "title".to_string(),
+        "runtime".to_string(),
+        "streamdetails".to_string(),
+        "file".to_string(),
+        "premiered".to_string(),
+        "rating".to_string(),
+        "playcount".to_string(),
+        "set".to_string(),
+        "dateadded".to_string(),
+        "tag".to_string(),
+        "genre".to_string(),
+        "cast".to_string(),
+        "plot".to_string(),
+    ]
+}
+
+fn non_empty(value: &str) -> Option<String> {
+    match value {
+        "" => None,
+        value => Some(value.to_string()),
+    }
+}
+
+impl From<MovieDetailsResponse> for crate::Movie {
+    fn from(movie: MovieDetailsResponse) -> Self {
+        let video = movie.streamdetails.video.first();
+        let resolution = video.map(|stream| {
+            if stream.height < 600 {
+                crate::Resolution::Sd
+            } else if stream.height <= 720 {
+                crate::Resolution::Hd720p
+            } else if stream.height <= 1080 {
+                crate::Resolution::Hd1080p
+            } else if stream.height <= 2160 {
+                crate::Resolution::Uhd4k
+            } else {
+                crate::Resolution::Uhd8k
+            }
+        });
+
+        crate::Movie {
+            id: movie.movieid,
+            title: movie.title,
+            runtime: movie.runtime,
+            path: movie.file,
+            premiered: movie.premiered,
+            dateadded: movie.dateadded,
+            resolution,
+            poster: movie.art.poster.map(encode_image_url),
+            fanart: movie.art.fanart.map(encode_image_url),
+            set_poster: movie.art.set_poster.map(encode_image_url),
+            set_fanart: movie.art.set_fanart.map(encode_image_url),
+            plot: movie.plot,
+            video_codec: video.and_then(|stream| non_empty(&stream.codec)),
+            hdr: video.and_then(|stream| non_empty(&stream.hdrtype)),
+            stereomode: video.and_then(|stream| non_empty(&stream.stereomode)),
+            aspect: video
+                .map(|stream| stream.aspect)
+                .filter(|aspect| *aspect > 0.0),
+            audio: movie
+                .streamdetails
+                .audio
+                .iter()
+                .map(|stream| crate::AudioStream {
+                    channels: stream.channels,
+                    codec: stream.codec.clone(),
+                    language: non_empty(&stream.language),
+                })
+                .collect(),
+            subtitle_languages: movie
+                .streamdetails
+                .subtitle
+                .iter()
+                .filter_map(|stream| non_empty(&stream.language))
+                .collect(),
+            rating: movie.rating,
+            playcount: movie.playcount,
+            set: match movie.set.as_ref() {
+                "" => None,
+                set => Some(set.to_string()),
+            },
+            tags: movie.tag,
+            genres: movie.genre,
+            cast: movie
+                .cast
+                .into_iter()
+                .map(|cast| crate::Cast {
+                    name: cast.name,
+                    role: cast.role,
+                    thumbnail: cast.thumbnail,
+                })
+                .collect(),
+        }
+    }
+}
+
 impl KodiRPC {
     #[instrument(err, level = "info")]
-    pub async fn get_all_movies(&self) -> Result<Vec<crate::Movie>, Box<dyn std::error::Error>> {
+    pub async fn get_all_movies(&self) -> Result<Vec<crate::Movie>, KodiRpcError> {
         event!(Level::TRACE, "Preparing RPC request");
         let data = self
             .send_rpc_request::<JsonRPCGetMoviesRequestParams, MoviesResponse>(&JsonRPCRequest {
@@ -105,82 +222,36 @@ impl KodiRPC {
                 id: 1,
                 method: "VideoLibrary.GetMovies".to_string(),
                 params: Some(JsonRPCGetMoviesRequestParams {
-                    properties: vec![
-                        "art".to_string(),
-                        "title".to_string(),
-                        "runtime".to_string(),
-                        "streamdetails".to_string(),
-                        "file".to_string(),
-                        "premiered".to_string(),
-                        "rating".to_string(),
-                        "playcount".to_string(),
-                        "set".to_string(),
-                        "dateadded".to_string(),
-                        "tag".to_string(),
-                        "genre".to_string(),
-                        "cast".to_string(),
-                    ],
+                    properties: movie_properties(),
                     limits: Some(JsonRPCRequestLimits { end: 10000 }),
                 }),
             })
             .await?;
         event!(Level::INFO, "found movies: {}", data.movies.len());
 
-        let mut movies: Vec<crate::Movie> = data
-            .movies
-            .into_iter()
-            .map(|movie| {
-                let resolution = movie.streamdetails.video.get(0).map(|stream| {
-                    if stream.height < 600 {
-                        crate::Resolution::Sd
-                    } else if stream.height <= 720 {
-                        crate::Resolution::Hd720p
-                    } else if stream.height <= 1080 {
-                        crate::Resolution::Hd1080p
-                    } else if stream.height <= 2160 {
-                        crate::Resolution::Uhd4k
-                    } else {
-                        crate::Resolution::Uhd8k
-                    }
-                });
-
-                crate::Movie {
-                    id: movie.movieid,
-                    title: movie.title,
-                    runtime: movie.runtime,
-                    path: movie.file,
-                    premiered: movie.premiered,
-                    dateadded: movie.dateadded,
-                    resolution,
-                    poster: movie.art.poster.map(|url| {
-                        percent_encoding::percent_encode(
-                            url.as_bytes(),
-                            percent_encoding::NON_ALPHANUMERIC,
-                        )
-                        .to_string()
-                    }),
-                    rating: movie.rating,
-                    playcount: movie.playcount,
-                    set: match movie.set.as_ref() {
-                        "" => None,
-                        set => Some(set.to_string()),
-                    },
-                    tags: movie.tag,
-                    genres: movie.genre,
-                    cast: movie
-                        .cast
-                        .into_iter()
-                        .map(|cast| crate::Cast {
-                            name: cast.name,
-                            role: cast.role,
-                            thumbnail: cast.thumbnail,
-                        })
-                        .collect(),
-                }
-            })
-            .collect();
+        let mut movies: Vec<crate::Movie> = data.movies.into_iter().map(From::from).collect();
 
         movies.sort_by(|a, b| a.title.cmp(&b.title));
         Ok(movies)
     }
+
+    #[instrument(err, level = "info")]
+    pub async fn get_movie_details(&self, movie_id: u16) -> Result<crate::Movie, KodiRpcError> {
+        event!(Level::TRACE, "Preparing RPC request");
+        let data = self
+            .send_rpc_request::<JsonRPCGetMovieDetailsRequestParams, MovieResponse>(
+                &JsonRPCRequest {
+                    jsonrpc: "2.0".to_string(),
+                    id: 1,
+                    method: "VideoLibrary.GetMovieDetails".to_string(),
+                    params: Some(JsonRPCGetMovieDetailsRequestParams {
+                        movieid: movie_id,
+                        properties: movie_properties(),
+                    }),
+                },
+            )
+            .await?;
+
+        Ok(data.moviedetails.into())
+    }
 }
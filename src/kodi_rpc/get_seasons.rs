@@ -0,0 +1,73 @@
+use serde::{Deserialize, Serialize};
+use tracing::{event, instrument, Level};
+
+use super::*;
+
+#[derive(Serialize, Clone, Debug)]
+struct JsonRPCGetSeasonsRequestParams {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    tvshowid: Option<u16>,
+    properties: Vec<String>,
+}
+
+#[derive(Deserialize, Clone, Debug)]
+#[serde(deny_unknown_fields)]
+struct SeasonDetailsResponse {
+    art: std::collections::HashMap<String, String>,
+    #[allow(dead_code)]
+    label: String,
+    seasonid: u32,
+    tvshowid: u16,
+    season: u16,
+    episode: u16,
+    watchedepisodes: u16,
+}
+
+#[derive(Deserialize, Clone, Debug)]
+struct SeasonsResponse {
+    #[serde(default)]
+    seasons: Vec<SeasonDetailsResponse>,
+}
+
+impl KodiRPC {
+    /// Get the seasons of a TV show, or of all TV shows, without their episodes
+    #[instrument(err, level = "info")]
+    pub async fn get_seasons(
+        &self,
+        tvshow_id: Option<u16>,
+    ) -> Result<Vec<crate::Season>, KodiRpcError> {
+        event!(Level::TRACE, "Preparing RPC request");
+        let data = self
+            .send_rpc_request::<JsonRPCGetSeasonsRequestParams, SeasonsResponse>(&JsonRPCRequest {
+                jsonrpc: "2.0".to_string(),
+                id: 1,
+                method: "VideoLibrary.GetSeasons".to_string(),
+                params: Some(JsonRPCGetSeasonsRequestParams {
+                    tvshowid: tvshow_id,
+                    properties: vec![
+                        "art".to_string(),
+                        "tvshowid".to_string(),
+                        "season".to_string(),
+                        "episode".to_string(),
+                        "watchedepisodes".to_string(),
+                    ],
+                }),
+            })
+            .await?;
+        event!(Level::INFO, "found seasons: {}", data.seasons.len());
+
+        Ok(data
+            .seasons
+            .into_iter()
+            .map(|season| crate::Season {
+                id: season.seasonid,
+                tvshow_id: season.tvshowid,
+                season: season.season,
+                poster: season.art.get("poster").cloned().map(encode_image_url),
+                episode_count: season.episode,
+                watched_episodes: season.watchedepisodes,
+                episodes: vec![],
+            })
+            .collect())
+    }
+}
@@ -0,0 +1,86 @@
+use serde::{Deserialize, Serialize};
+use tracing::{event, instrument, Level};
+
+use super::*;
+
+#[derive(Serialize, Clone, Debug)]
+struct JsonRPCGetSongsRequestParams {
+    properties: Vec<String>,
+}
+
+#[derive(Deserialize, Clone, Debug)]
+#[serde(deny_unknown_fields)]
+struct SongDetailsResponse {
+    #[allow(dead_code)]
+    label: String,
+    songid: u32,
+    title: String,
+    artist: Vec<String>,
+    album: String,
+    albumid: u32,
+    track: u16,
+    duration: u32,
+    file: String,
+    year: u16,
+    playcount: u16,
+}
+
+#[derive(Deserialize, Clone, Debug)]
+struct SongsResponse {
+    #[serde(default)]
+    songs: Vec<SongDetailsResponse>,
+}
+
+impl KodiRPC {
+    #[instrument(err, level = "info")]
+    pub async fn get_all_songs(&self) -> Result<Vec<crate::Song>, KodiRpcError> {
+        event!(Level::TRACE, "Preparing RPC request");
+        let data = self
+            .send_rpc_request::<JsonRPCGetSongsRequestParams, SongsResponse>(&JsonRPCRequest {
+                jsonrpc: "2.0".to_string(),
+                id: 1,
+                method: "AudioLibrary.GetSongs".to_string(),
+                params: Some(JsonRPCGetSongsRequestParams {
+                    properties: vec![
+                        "title".to_string(),
+                        "artist".to_string(),
+                        "album".to_string(),
+                        "albumid".to_string(),
+                        "track".to_string(),
+                        "duration".to_string(),
+                        "file".to_string(),
+                        "year".to_string(),
+                        "playcount".to_string(),
+                    ],
+                }),
+            })
+            .await?;
+        event!(Level::INFO, "found songs: {}", data.songs.len());
+
+        let mut songs: Vec<crate::Song> = data
+            .songs
+            .into_iter()
+            .map(|song| crate::Song {
+                id: song.songid,
+                title: song.title,
+                artists: song.artist,
+                album: match song.album.as_ref() {
+                    "" => None,
+                    album => Some(album.to_string()),
+                },
+                album_id: match song.albumid {
+                    0 => None,
+                    album_id => Some(album_id),
+                },
+                track: song.track,
+                duration: song.duration,
+                path: song.file,
+                year: song.year,
+                playcount: song.playcount,
+            })
+            .collect();
+
+        songs.sort_by(|a, b| a.title.cmp(&b.title));
+        Ok(songs)
+    }
+}
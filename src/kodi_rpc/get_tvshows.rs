@@ -0,0 +1,82 @@
+use serde::{Deserialize, Serialize};
+use tracing::{event, instrument, Level};
+
+use super::*;
+
+#[derive(Serialize, Clone, Debug)]
+struct JsonRPCGetTvShowsRequestParams {
+    properties: Vec<String>,
+}
+
+#[derive(Deserialize, Clone, Debug)]
+#[serde(deny_unknown_fields)]
+struct TvShowDetailsResponse {
+    art: std::collections::HashMap<String, String>,
+    #[allow(dead_code)]
+    label: String,
+    tvshowid: u16,
+    title: String,
+    file: String,
+    premiered: String,
+    rating: f32,
+    episode: u16,
+    watchedepisodes: u16,
+    tag: Vec<String>,
+    genre: Vec<String>,
+}
+
+#[derive(Deserialize, Clone, Debug)]
+struct TvShowsResponse {
+    #[serde(default)]
+    tvshows: Vec<TvShowDetailsResponse>,
+}
+
+impl KodiRPC {
+    /// Get all TV shows, without their seasons
+    #[instrument(err, level = "info")]
+    pub async fn get_all_tvshows(&self) -> Result<Vec<crate::TvShow>, KodiRpcError> {
+        event!(Level::TRACE, "Preparing RPC request");
+        let data = self
+            .send_rpc_request::<JsonRPCGetTvShowsRequestParams, TvShowsResponse>(&JsonRPCRequest {
+                jsonrpc: "2.0".to_string(),
+                id: 1,
+                method: "VideoLibrary.GetTVShows".to_string(),
+                params: Some(JsonRPCGetTvShowsRequestParams {
+                    properties: vec![
+                        "art".to_string(),
+                        "title".to_string(),
+                        "file".to_string(),
+                        "premiered".to_string(),
+                        "rating".to_string(),
+                        "episode".to_string(),
+                        "watchedepisodes".to_string(),
+                        "tag".to_string(),
+                        "genre".to_string(),
+                    ],
+                }),
+            })
+            .await?;
+        event!(Level::INFO, "found TV shows: {}", data.tvshows.len());
+
+        let mut tvshows: Vec<crate::TvShow> = data
+            .tvshows
+            .into_iter()
+            .map(|tvshow| crate::TvShow {
+                id: tvshow.tvshowid,
+                title: tvshow.title,
+                path: tvshow.file,
+                premiered: tvshow.premiered,
+                poster: tvshow.art.get("poster").cloned().map(encode_image_url),
+                rating: tvshow.rating,
+                episode_count: tvshow.episode,
+                watched_episodes: tvshow.watchedepisodes,
+                tags: tvshow.tag,
+                genres: tvshow.genre,
+                seasons: vec![],
+            })
+            .collect();
+
+        tvshows.sort_by(|a, b| a.title.cmp(&b.title));
+        Ok(tvshows)
+    }
+}
@@ -2,21 +2,43 @@ use serde::{Deserialize, Serialize};
 use tracing::{event, instrument, Level};
 
 mod clean_video_library;
+mod error;
+mod get_albums;
+mod get_artists;
 mod get_directory;
+mod get_episodes;
+mod get_file_details;
 mod get_info_booleans;
 mod get_movies;
+mod get_seasons;
+mod get_songs;
+mod get_tvshows;
+mod notifications;
+mod ping;
 mod refresh_movie;
+mod remove_movie;
 mod scan_library;
 mod set_movie_details;
 
+pub use error::{JsonRPCError, JsonRPCErrorData, KodiRpcError};
+pub use notifications::Notification;
+pub use ping::Version;
+pub use set_movie_details::MovieDetails;
+
 pub struct KodiRPC {
     client: reqwest::Client,
     host: String,
+    username: Option<String>,
+    password: Option<String>,
+    timeout: std::time::Duration,
 }
 
 impl std::fmt::Debug for KodiRPC {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        f.debug_struct("KodiRPC").field("host", &self.host).finish()
+        f.debug_struct("KodiRPC")
+            .field("host", &self.host)
+            .field("username", &self.username)
+            .finish()
     }
 }
 
@@ -29,54 +51,129 @@ struct JsonRPCRequest<T> {
     params: Option<T>,
 }
 
-#[derive(Deserialize, Clone, Debug)]
-#[serde(untagged)]
-enum JsonRPCResponse<T> {
-    Success { result: T },
-    Error { error: JsonRPCError },
+#[derive(Serialize, Clone, Debug)]
+struct JsonRPCRequestLimits {
+    end: u16,
 }
 
 #[derive(Deserialize, Clone, Debug)]
-struct JsonRPCErrorData {
-    message: String,
-    method: String,
+struct JsonRPCResponse {
+    result: Option<serde_json::Value>,
+    error: Option<JsonRPCError>,
 }
 
-#[derive(Deserialize, Clone, Debug)]
-struct JsonRPCError {
-    message: String,
-    data: Option<JsonRPCErrorData>,
+/// Encode an image url from Kodi so that it can be used as a path segment
+fn encode_image_url(url: String) -> String {
+    percent_encoding::percent_encode(url.as_bytes(), percent_encoding::NON_ALPHANUMERIC).to_string()
 }
-impl std::fmt::Display for JsonRPCError {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        if let Some(ref data) = self.data {
-            write!(
-                f,
-                "{} when calling {}: {}",
-                self.message, data.method, data.message
-            )
-        } else {
-            write!(f, "{} ", self.message)
+
+/// The audio library returns an empty string when there is no image
+fn non_empty_image_url(url: String) -> Option<String> {
+    if url.is_empty() {
+        None
+    } else {
+        Some(encode_image_url(url))
+    }
+}
+
+/// HTTP client of a Kodi instance, trusting its CA certificate
+fn build_client(kodi: &crate::Kodi) -> Result<reqwest::Client, KodiRpcError> {
+    let mut client = reqwest::Client::builder()
+        .use_rustls_tls()
+        .danger_accept_invalid_certs(kodi.accept_invalid_certs);
+    if let Some(ca_certificate) = kodi.ca_certificate.as_ref() {
+        let pem = std::fs::read(ca_certificate).map_err(|err| {
+            KodiRpcError::Configuration(format!(
+                "can't read CA certificate {}: {}",
+                ca_certificate, err
+            ))
+        })?;
+        let certificate = reqwest::Certificate::from_pem(&pem).map_err(|err| {
+            KodiRpcError::Configuration(format!(
+                "invalid CA certificate {}: {}",
+                ca_certificate, err
+            ))
+        })?;
+        client = client.add_root_certificate(certificate);
+    }
+    Ok(client.build()?)
+}
+
+/// HTTP clients of the Kodi instances by name, built once when the configuration is loaded so
+/// that requests share their connections. A client that can't be built keeps its error, returned
+/// on each use until the next load.
+#[derive(Default)]
+pub struct KodiClients {
+    clients: std::collections::HashMap<String, Result<reqwest::Client, String>>,
+}
+
+impl KodiClients {
+    pub fn from_config(config: &crate::Config) -> KodiClients {
+        KodiClients {
+            clients: config
+                .kodis
+                .iter()
+                .map(|kodi| {
+                    (
+                        kodi.name.clone(),
+                        build_client(kodi).map_err(|err| match err {
+                            KodiRpcError::Configuration(message) => message,
+                            err => err.to_string(),
+                        }),
+                    )
+                })
+                .collect(),
+        }
+    }
+
+    /// RPC client of a Kodi instance with its shared HTTP client, built on the spot for an
+    /// instance unknown when the configuration was loaded
+    pub fn rpc(&self, kodi: &crate::Kodi) -> Result<KodiRPC, KodiRpcError> {
+        match self.clients.get(&kodi.name) {
+            Some(Ok(client)) => Ok(KodiRPC::with_client(kodi, client.clone())),
+            Some(Err(err)) => Err(KodiRpcError::Configuration(err.clone())),
+            None => KodiRPC::new(kodi),
         }
     }
 }
-impl std::error::Error for JsonRPCError {}
 
 impl KodiRPC {
-    /// Create a new Kodi RPC client
-    pub fn new(host: &str) -> KodiRPC {
+    /// Create a new Kodi RPC client, with the credentials and TLS settings of the instance
+    pub fn new(kodi: &crate::Kodi) -> Result<KodiRPC, KodiRpcError> {
+        Ok(KodiRPC::with_client(kodi, build_client(kodi)?))
+    }
+
+    /// Create a new Kodi RPC client sending its requests with an existing HTTP client
+    pub fn with_client(kodi: &crate::Kodi, client: reqwest::Client) -> KodiRPC {
         KodiRPC {
-            client: reqwest::Client::new(),
-            host: host.to_string(),
+            client,
+            host: kodi.url.clone(),
+            username: kodi.username.clone(),
+            password: kodi.password.clone(),
+            timeout: std::time::Duration::from_secs(kodi.timeout),
         }
     }
 
     /// Send RPC request
-    #[instrument(err, level = "info")]
     async fn send_rpc_request<Params, Resp>(
         &self,
         request: &JsonRPCRequest<Params>,
-    ) -> Result<Resp, Box<dyn std::error::Error>>
+    ) -> Result<Resp, KodiRpcError>
+    where
+        Params: Serialize + std::fmt::Debug,
+        for<'de> Resp: Deserialize<'de>,
+    {
+        self.send_rpc_request_with_timeout(request, self.timeout)
+            .await
+    }
+
+    /// Send RPC request, for methods that can take longer than the configured timeout
+    #[instrument(err, level = "info")]
+    async fn send_rpc_request_with_timeout<Params, Resp>(
+        &self,
+        request_body: &JsonRPCRequest<Params>,
+        timeout: std::time::Duration,
+    ) -> Result<Resp, KodiRpcError>
     where
         Params: Serialize + std::fmt::Debug,
         for<'de> Resp: Deserialize<'de>,
@@ -88,11 +185,11 @@ impl KodiRPC {
         //     "request to send: {:?}",
         //     serde_json::to_string(request)
         // );
-        let mut request = self
-            .client
-            .post(&format!("{}jsonrpc", self.host))
-            .json(request)
-            .build()?;
+        let mut request = self.client.post(&format!("{}jsonrpc", self.host));
+        if let Some(username) = self.username.as_ref() {
+            request = request.basic_auth(username, self.password.as_ref());
+        }
+        let mut request = request.json(request_body).timeout(timeout).build()?;
         let headers = request.headers_mut();
 
         headers.insert(
@@ -100,23 +197,40 @@ impl KodiRPC {
             reqwest::header::HeaderValue::from_static("application/json"),
         );
         event!(Level::TRACE, "Sending RPC Request");
-        let data = self
-            .client
-            .execute(request)
-            .await?
-            .json::<JsonRPCResponse<Resp>>()
-            // .text()
-            .await?;
+        let response = self.client.execute(request).await?;
+        match response.status() {
+            reqwest::StatusCode::UNAUTHORIZED | reqwest::StatusCode::FORBIDDEN => {
+                return Err(KodiRpcError::Unauthorized)
+            }
+            status if !status.is_success() => {
+                return Err(KodiRpcError::HttpStatus(status.as_u16()))
+            }
+            _ => (),
+        }
+        let body = response.bytes().await?;
+        let data = serde_json::from_slice::<JsonRPCResponse>(&body).map_err(|err| {
+            KodiRpcError::Deserialization {
+                field: ".".to_string(),
+                message: err.to_string(),
+            }
+        })?;
         event!(Level::TRACE, "done");
 
         // event!(Level::INFO, "response received: {:?}", data);
-        // let data: JsonRPCResponse<Resp> = serde_json::from_str(&data)?;
         match data {
-            JsonRPCResponse::Success { result } => Ok(result),
-            JsonRPCResponse::Error { error } => {
+            JsonRPCResponse {
+                error: Some(error), ..
+            } => {
                 event!(Level::ERROR, "Error sending JsonRPC Request: {}", error,);
-                Err(error)?
+                Err(error.into())
             }
+            JsonRPCResponse { result, .. } => serde_path_to_error::deserialize(
+                result.unwrap_or(serde_json::Value::Null),
+            )
+            .map_err(|err| KodiRpcError::Deserialization {
+                field: err.path().to_string(),
+                message: err.inner().to_string(),
+            }),
         }
     }
 }
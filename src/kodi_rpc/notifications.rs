@@ -0,0 +1,202 @@
+use futures::Stream;
+use serde::Deserialize;
+use tokio::io::AsyncReadExt;
+use tracing::{event, instrument, Level};
+
+use super::*;
+
+/// Library changes pushed by Kodi over its TCP JSON-RPC channel
+#[derive(Clone, Debug, PartialEq)]
+pub enum Notification {
+    VideoUpdated { item_type: String, id: u16 },
+    VideoRemoved { item_type: String, id: u16 },
+    VideoScanFinished,
+    VideoCleanFinished,
+}
+
+#[derive(Deserialize, Clone, Debug)]
+struct JsonRPCNotification {
+    method: String,
+    params: JsonRPCNotificationParams,
+}
+
+#[derive(Deserialize, Clone, Debug)]
+struct JsonRPCNotificationParams {
+    data: Option<NotificationData>,
+}
+
+#[derive(Deserialize, Clone, Debug)]
+#[serde(untagged)]
+enum NotificationData {
+    Item { item: NotificationItem },
+    Flat(NotificationItem),
+}
+
+#[derive(Deserialize, Clone, Debug)]
+struct NotificationItem {
+    id: u16,
+    #[serde(rename = "type")]
+    ty: String,
+}
+
+impl NotificationData {
+    fn into_item(self) -> NotificationItem {
+        match self {
+            NotificationData::Item { item } => item,
+            NotificationData::Flat(item) => item,
+        }
+    }
+}
+
+impl Notification {
+    fn from_json_rpc(notification: JsonRPCNotification) -> Option<Notification> {
+        let item = notification.params.data.map(NotificationData::into_item);
+        match (notification.method.as_ref(), item) {
+            ("VideoLibrary.OnUpdate", Some(item)) => Some(Notification::VideoUpdated {
+                item_type: item.ty,
+                id: item.id,
+            }),
+            ("VideoLibrary.OnRemove", Some(item)) => Some(Notification::VideoRemoved {
+                item_type: item.ty,
+                id: item.id,
+            }),
+            ("VideoLibrary.OnScanFinished", _) => Some(Notification::VideoScanFinished),
+            ("VideoLibrary.OnCleanFinished", _) => Some(Notification::VideoCleanFinished),
+            _ => None,
+        }
+    }
+}
+
+enum Parsed {
+    Message(serde_json::Value, usize),
+    Incomplete,
+    Invalid,
+}
+
+/// Kodi sends JSON objects one after the other without separator, so parse the first complete one
+fn parse_first(buffer: &[u8]) -> Parsed {
+    let mut stream = serde_json::Deserializer::from_slice(buffer).into_iter::<serde_json::Value>();
+    match stream.next() {
+        Some(Ok(value)) => Parsed::Message(value, stream.byte_offset()),
+        Some(Err(err)) if err.is_eof() => Parsed::Incomplete,
+        Some(Err(_)) => Parsed::Invalid,
+        None => Parsed::Incomplete,
+    }
+}
+
+impl KodiRPC {
+    /// Connect to the TCP JSON-RPC port of Kodi and listen to library notifications. The stream
+    /// ends when the connection is closed.
+    #[instrument(err, level = "info")]
+    pub async fn notifications(
+        &self,
+        port: u16,
+    ) -> Result<impl Stream<Item = Notification>, KodiRpcError> {
+        let url = reqwest::Url::parse(&self.host)
+            .map_err(|_| KodiRpcError::InvalidUrl(self.host.clone()))?;
+        let host = url.host_str().unwrap_or("localhost").to_string();
+        let connection = actix_rt::net::TcpStream::connect((host.as_ref(), port)).await?;
+        event!(
+            Level::INFO,
+            "listening to notifications from {}:{}",
+            host,
+            port
+        );
+
+        Ok(futures::stream::unfold(
+            (connection, Vec::new()),
+            |(mut connection, mut buffer)| async move {
+                loop {
+                    match parse_first(&buffer) {
+                        Parsed::Message(value, offset) => {
+                            buffer.drain(..offset);
+                            if let Some(notification) = serde_json::from_value(value)
+                                .ok()
+                                .and_then(Notification::from_json_rpc)
+                            {
+                                return Some((notification, (connection, buffer)));
+                            }
+                            continue;
+                        }
+                        Parsed::Invalid => {
+                            event!(Level::WARN, "dropping invalid notification data");
+                            buffer.clear();
+                        }
+                        Parsed::Incomplete => (),
+                    }
+
+                    let mut chunk = [0; 4096];
+                    match connection.read(&mut chunk).await {
+                        Ok(0) | Err(_) => return None,
+                        Ok(read) => buffer.extend_from_slice(&chunk[..read]),
+                    }
+                }
+            },
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn notification(message: &str) -> Option<Notification> {
+        serde_json::from_str(message)
+            .ok()
+            .and_then(Notification::from_json_rpc)
+    }
+
+    #[test]
+    fn parse_first_message() {
+        let first = r#"{"jsonrpc":"2.0","method":"VideoLibrary.OnScanFinished","params":{}}"#;
+        let buffer = format!("{}{}", first, r#"{"jsonrpc":"2.0","met"#);
+        match parse_first(buffer.as_bytes()) {
+            Parsed::Message(value, offset) => {
+                assert_eq!(offset, first.len());
+                assert_eq!(value["method"], "VideoLibrary.OnScanFinished");
+            }
+            _ => panic!("expected a message"),
+        }
+        assert!(matches!(
+            parse_first(&buffer.as_bytes()[first.len()..]),
+            Parsed::Incomplete
+        ));
+        assert!(matches!(parse_first(b""), Parsed::Incomplete));
+        assert!(matches!(parse_first(b"}{"), Parsed::Invalid));
+    }
+
+    #[test]
+    fn parse_notifications() {
+        let cases = vec![
+            (
+                r#"{"jsonrpc":"2.0","method":"VideoLibrary.OnUpdate","params":{"data":{"item":{"id":12,"type":"movie"}},"sender":"xbmc"}}"#,
+                Some(Notification::VideoUpdated {
+                    item_type: "movie".to_string(),
+                    id: 12,
+                }),
+            ),
+            (
+                r#"{"jsonrpc":"2.0","method":"VideoLibrary.OnRemove","params":{"data":{"id":7,"type":"episode"},"sender":"xbmc"}}"#,
+                Some(Notification::VideoRemoved {
+                    item_type: "episode".to_string(),
+                    id: 7,
+                }),
+            ),
+            (
+                r#"{"jsonrpc":"2.0","method":"VideoLibrary.OnCleanFinished","params":{"data":null,"sender":"xbmc"}}"#,
+                Some(Notification::VideoCleanFinished),
+            ),
+            (
+                r#"{"jsonrpc":"2.0","method":"VideoLibrary.OnUpdate","params":{"data":null,"sender":"xbmc"}}"#,
+                None,
+            ),
+            (
+                r#"{"jsonrpc":"2.0","method":"Player.OnPlay","params":{"data":{"item":{"id":1,"type":"movie"}},"sender":"xbmc"}}"#,
+                None,
+            ),
+        ];
+        for (message, expected) in cases {
+            assert_eq!(notification(message), expected, "{}", message);
+        }
+    }
+}
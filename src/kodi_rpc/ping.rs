@@ -0,0 +1,54 @@
+use serde::Deserialize;
+use tracing::{event, instrument, Level};
+
+use super::*;
+
+#[derive(Deserialize, Clone, Debug)]
+struct VersionResponse {
+    version: Version,
+}
+
+/// Version of the JSON-RPC API of Kodi
+#[derive(Deserialize, Clone, Debug)]
+pub struct Version {
+    pub major: u32,
+    pub minor: u32,
+    pub patch: u32,
+}
+
+impl std::fmt::Display for Version {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}.{}.{}", self.major, self.minor, self.patch)
+    }
+}
+
+impl KodiRPC {
+    #[instrument(err, level = "info")]
+    pub async fn ping(&self) -> Result<(), KodiRpcError> {
+        event!(Level::TRACE, "Preparing RPC request");
+        self.send_rpc_request::<(), String>(&JsonRPCRequest {
+            jsonrpc: "2.0".to_string(),
+            id: 1,
+            method: "JSONRPC.Ping".to_string(),
+            params: None,
+        })
+        .await?;
+
+        Ok(())
+    }
+
+    #[instrument(err, level = "info")]
+    pub async fn get_version(&self) -> Result<Version, KodiRpcError> {
+        event!(Level::TRACE, "Preparing RPC request");
+        let data = self
+            .send_rpc_request::<(), VersionResponse>(&JsonRPCRequest {
+                jsonrpc: "2.0".to_string(),
+                id: 1,
+                method: "JSONRPC.Version".to_string(),
+                params: None,
+            })
+            .await?;
+
+        Ok(data.version)
+    }
+}
@@ -10,7 +10,7 @@ struct JsonRPCRefreshMovieRequestParams {
 
 impl KodiRPC {
     #[instrument(err, level = "info")]
-    pub async fn refresh_movie(&self, movie_id: u16) -> Result<(), Box<dyn std::error::Error>> {
+    pub async fn refresh_movie(&self, movie_id: u16) -> Result<(), KodiRpcError> {
         event!(Level::TRACE, "Preparing RPC request");
         self.send_rpc_request::<JsonRPCRefreshMovieRequestParams, String>(&JsonRPCRequest {
             jsonrpc: "2.0".to_string(),
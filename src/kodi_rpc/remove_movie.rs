@@ -0,0 +1,27 @@
+use serde::Serialize;
+use tracing::{event, instrument, Level};
+
+use super::*;
+
+#[derive(Serialize, Clone, Debug)]
+struct JsonRPCRemoveMovieRequestParams {
+    movieid: u16,
+}
+
+impl KodiRPC {
+    /// Remove a movie from the library, its file is not touched
+    #[instrument(err, level = "info")]
+    pub async fn remove_movie(&self, movie_id: u16) -> Result<(), KodiRpcError> {
+        event!(Level::TRACE, "Preparing RPC request");
+        self.send_rpc_request::<JsonRPCRemoveMovieRequestParams, String>(&JsonRPCRequest {
+            jsonrpc: "2.0".to_string(),
+            id: 1,
+            method: "VideoLibrary.RemoveMovie".to_string(),
+            params: Some(JsonRPCRemoveMovieRequestParams { movieid: movie_id }),
+        })
+        .await?;
+        event!(Level::INFO, "Removed movie {}", movie_id);
+
+        Ok(())
+    }
+}
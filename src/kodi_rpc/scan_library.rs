@@ -1,10 +1,16 @@
+use serde::Serialize;
 use tracing::{event, instrument, Level};
 
 use super::*;
 
+#[derive(Serialize, Clone, Debug)]
+struct JsonRPCScanDirectoryRequestParams {
+    directory: String,
+}
+
 impl KodiRPC {
     #[instrument(err, level = "info")]
-    pub async fn scan_video_library(&self) -> Result<(), Box<dyn std::error::Error>> {
+    pub async fn scan_video_library(&self) -> Result<(), KodiRpcError> {
         event!(Level::TRACE, "Preparing RPC request to start scan");
         self.send_rpc_request::<(), String>(&JsonRPCRequest {
             jsonrpc: "2.0".to_string(),
@@ -16,10 +22,24 @@ impl KodiRPC {
         Ok(())
     }
 
+    /// Scan only a directory, faster than the whole library
+    #[instrument(err, level = "info")]
+    pub async fn scan_video_directory(&self, directory: &str) -> Result<(), KodiRpcError> {
+        event!(Level::TRACE, "Preparing RPC request to start scan");
+        self.send_rpc_request::<JsonRPCScanDirectoryRequestParams, String>(&JsonRPCRequest {
+            jsonrpc: "2.0".to_string(),
+            id: 1,
+            method: "VideoLibrary.Scan".to_string(),
+            params: Some(JsonRPCScanDirectoryRequestParams {
+                directory: directory.to_string(),
+            }),
+        })
+        .await?;
+        Ok(())
+    }
+
     #[instrument(err, level = "info")]
-    pub async fn scan_video_library_and_wait_for_done(
-        &self,
-    ) -> Result<(), Box<dyn std::error::Error>> {
+    pub async fn scan_video_library_and_wait_for_done(&self) -> Result<(), KodiRpcError> {
         event!(Level::TRACE, "Preparing RPC request to start scan");
         self.scan_video_library().await?;
         event!(Level::DEBUG, "Scanning Kodi Library");
@@ -3,10 +3,22 @@ use tracing::{event, instrument, Level};
 
 use super::*;
 
+/// Movie details to set, fields left to `None` are not changed
+#[derive(Serialize, Clone, Debug, Default)]
+pub struct MovieDetails {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub tag: Option<Vec<String>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub playcount: Option<u8>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub set: Option<String>,
+}
+
 #[derive(Serialize, Clone, Debug)]
 struct JsonRPCSetMovieDetailRequestParams {
     movieid: u16,
-    tag: Vec<String>,
+    #[serde(flatten)]
+    details: MovieDetails,
 }
 
 impl KodiRPC {
@@ -14,8 +26,8 @@ impl KodiRPC {
     pub async fn set_movie_details(
         &self,
         movie_id: u16,
-        tags: Vec<String>,
-    ) -> Result<String, Box<dyn std::error::Error>> {
+        details: MovieDetails,
+    ) -> Result<String, KodiRpcError> {
         event!(Level::TRACE, "Preparing RPC request");
         let data = self
             .send_rpc_request::<JsonRPCSetMovieDetailRequestParams, String>(&JsonRPCRequest {
@@ -24,7 +36,7 @@ impl KodiRPC {
                 method: "VideoLibrary.SetMovieDetails".to_string(),
                 params: Some(JsonRPCSetMovieDetailRequestParams {
                     movieid: movie_id,
-                    tag: tags,
+                    details,
                 }),
             })
             .await?;
@@ -1,27 +1,85 @@
 use lazy_static::lazy_static;
 use serde::{Deserialize, Serialize};
-use tracing::instrument;
+use tracing::{event, instrument, Level};
 
 pub mod api;
+pub mod auth;
+pub mod cache;
+pub mod config;
+pub mod events;
+pub mod file_operations;
+pub mod filename;
+pub mod hospital;
+pub mod jobs;
 pub mod kodi_rpc;
+pub mod sources;
+pub mod stats;
 
 lazy_static! {
     pub static ref CONFIG: std::sync::Arc<std::sync::RwLock<Config>> =
         std::sync::Arc::new(std::sync::RwLock::new(Config::default()));
 }
 
+/// Time to wait for more changes before saving the cache, so that a burst of notifications is
+/// saved once
+const CACHE_SAVE_DELAY: std::time::Duration = std::time::Duration::from_secs(5);
+
+/// Whether a save of the cache is waiting for [`CACHE_SAVE_DELAY`]
+static CACHE_SAVE_SCHEDULED: std::sync::atomic::AtomicBool =
+    std::sync::atomic::AtomicBool::new(false);
+
 #[derive(Deserialize, Serialize, Clone, Debug)]
 pub struct Config {
     pub kodis: Vec<Kodi>,
     pub filepatterns_to_ignore: Vec<String>,
-    pub movies_directory: String,
+    /// Folder with movies, see `movie_sources` for more folders
+    #[serde(default)]
+    pub movies_directory: Option<String>,
+    /// Folders with movies, with their own depth, ignored files and patterns
+    #[serde(default)]
+    pub movie_sources: Vec<sources::MovieSource>,
     pub name_differences_threshold: Option<usize>,
-    #[serde(default = "get_default_movie_pattern")]
-    pub movie_pattern: String,
+    /// Patterns finding the title and year of movies from their file or folder names, tried in
+    /// order before the default ones
+    #[serde(default)]
+    pub movie_patterns: Vec<String>,
+    /// Legacy pattern, matched on the path relative to the movies directory
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub movie_pattern: Option<String>,
+    #[serde(default)]
+    pub tvshows_directory: Option<String>,
+    /// File where the last known movie lists and their history are saved, relative to the working
+    /// directory. Nothing is saved without it, the lists are fetched again on startup.
+    #[serde(default)]
+    pub cache_file: Option<String>,
+    /// Users and API tokens allowed to use the web server, open to everyone when absent
+    #[serde(default, skip_serializing)]
+    pub auth: Option<auth::AuthConfig>,
+    #[serde(default)]
+    pub server: ServerConfig,
+    /// Languages the audio and subtitles of movies must be in
+    #[serde(default)]
+    pub language_rules: Vec<hospital::LanguageRule>,
+    /// Where the folders seen by Kodi are mounted locally, to list files and read their sizes
+    /// without going through Kodi, and needed to rename, move and delete files
+    #[serde(default)]
+    pub path_mappings: Vec<PathMapping>,
+}
+
+/// A folder as seen by Kodi, and where it is mounted locally
+#[derive(Deserialize, Serialize, Clone, Debug)]
+pub struct PathMapping {
+    pub kodi: String,
+    pub local: String,
 }
 
-fn get_default_movie_pattern() -> String {
-    "(?P<title>.+?)( (?P<year>[0-9]{4})\\.[a-z0-9]{3,4}$|\\.[a-z0-9]{3,4}$)".to_string()
+impl PathMapping {
+    /// Local path of a file seen by Kodi, if it is in this folder
+    pub fn local_path(&self, kodi_path: &str) -> Option<String> {
+        sources::normalize_path(kodi_path)
+            .strip_prefix(&sources::normalize_path(&self.kodi))
+            .map(|rest| format!("{}{}", self.local, rest))
+    }
 }
 
 impl Default for Config {
@@ -30,22 +88,173 @@ impl Default for Config {
             kodis: vec![Kodi {
                 name: "localhost".to_string(),
                 url: "http://localhost:8080".to_string(),
+                tcp_port: get_default_tcp_port(),
+                username: None,
+                password: None,
+                ca_certificate: None,
+                accept_invalid_certs: false,
+                timeout: get_default_timeout(),
             }],
             filepatterns_to_ignore: vec![],
-            movies_directory: "/movies/".to_string(),
+            movies_directory: Some("/movies/".to_string()),
+            movie_sources: vec![],
             name_differences_threshold: None,
-            movie_pattern: get_default_movie_pattern(),
+            movie_patterns: vec![],
+            movie_pattern: None,
+            tvshows_directory: None,
+            cache_file: None,
+            auth: None,
+            server: ServerConfig::default(),
+            language_rules: vec![],
+            path_mappings: vec![],
         }
     }
 }
 
+/// Settings of the web server, they are only read on startup
 #[derive(Deserialize, Serialize, Clone, Debug)]
+pub struct ServerConfig {
+    #[serde(default = "get_default_bind_addresses")]
+    pub bind_addresses: Vec<String>,
+    #[serde(default = "get_default_port")]
+    pub port: u16,
+    /// Folder with the UI
+    #[serde(default = "get_default_static_dir")]
+    pub static_dir: String,
+    /// Prefix of all routes, to run behind a reverse proxy under a path like `/kodi-helper/`
+    #[serde(default = "get_default_base_url")]
+    pub base_url: String,
+    /// Time between two refreshes of the libraries, in seconds
+    #[serde(default = "get_default_refresh_interval")]
+    pub refresh_interval: u64,
+    /// Serve over https with this certificate
+    #[serde(default)]
+    pub tls: Option<TlsConfig>,
+}
+
+#[derive(Deserialize, Serialize, Clone, Debug)]
+pub struct TlsConfig {
+    /// PEM file with the certificate chain
+    pub cert: String,
+    /// PEM file with the private key, PKCS8 or RSA
+    pub key: String,
+}
+
+fn get_default_bind_addresses() -> Vec<String> {
+    vec!["0.0.0.0".to_string()]
+}
+
+fn get_default_port() -> u16 {
+    8080
+}
+
+fn get_default_static_dir() -> String {
+    "./static/".to_string()
+}
+
+fn get_default_base_url() -> String {
+    "/".to_string()
+}
+
+fn get_default_refresh_interval() -> u64 {
+    60 * 30
+}
+
+impl Default for ServerConfig {
+    fn default() -> Self {
+        ServerConfig {
+            bind_addresses: get_default_bind_addresses(),
+            port: get_default_port(),
+            static_dir: get_default_static_dir(),
+            base_url: get_default_base_url(),
+            refresh_interval: get_default_refresh_interval(),
+            tls: None,
+        }
+    }
+}
+
+impl ServerConfig {
+    /// The base url without its trailing slash, empty when running at the root
+    pub fn route_prefix(&self) -> String {
+        let prefix = self.base_url.trim_matches('/');
+        if prefix.is_empty() {
+            String::new()
+        } else {
+            format!("/{}", prefix)
+        }
+    }
+}
+
+impl Config {
+    /// Get a configured Kodi instance by its name
+    pub fn get_kodi(&self, name: &str) -> Option<&Kodi> {
+        self.kodis.iter().find(|kodi| kodi.name == name)
+    }
+
+    /// Local path of a file seen by Kodi, if its folder is mapped. Paths are compared once
+    /// normalized, the first matching mapping is used.
+    pub fn local_path(&self, kodi_path: &str) -> Option<String> {
+        self.path_mapping(kodi_path)
+            .map(|(_, local_path)| local_path)
+    }
+
+    /// The mapping of the folder of a file seen by Kodi, with the local path of the file
+    pub fn path_mapping(&self, kodi_path: &str) -> Option<(&PathMapping, String)> {
+        self.path_mappings.iter().find_map(|mapping| {
+            mapping
+                .local_path(kodi_path)
+                .map(|local_path| (mapping, local_path))
+        })
+    }
+}
+
+#[derive(Deserialize, Serialize, Clone)]
 pub struct Kodi {
     pub name: String,
     pub url: String,
+    /// Port of the TCP JSON-RPC channel used to receive library notifications, `None` to disable
+    #[serde(default = "get_default_tcp_port")]
+    pub tcp_port: Option<u16>,
+    /// Credentials of the Kodi web server
+    #[serde(default)]
+    pub username: Option<String>,
+    #[serde(default, skip_serializing)]
+    pub password: Option<String>,
+    /// PEM file of an additional certificate authority to trust for an `https` url
+    #[serde(default)]
+    pub ca_certificate: Option<String>,
+    /// Accept invalid certificates for an `https` url, like self-signed ones
+    #[serde(default)]
+    pub accept_invalid_certs: bool,
+    /// Timeout of a request to Kodi, in seconds
+    #[serde(default = "get_default_timeout")]
+    pub timeout: u64,
 }
 
-#[derive(Serialize, Clone, Debug)]
+// Kodi instances end up in traces, keep the password out of them
+impl std::fmt::Debug for Kodi {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Kodi")
+            .field("name", &self.name)
+            .field("url", &self.url)
+            .field("tcp_port", &self.tcp_port)
+            .field("username", &self.username)
+            .field("ca_certificate", &self.ca_certificate)
+            .field("accept_invalid_certs", &self.accept_invalid_certs)
+            .field("timeout", &self.timeout)
+            .finish()
+    }
+}
+
+fn get_default_tcp_port() -> Option<u16> {
+    Some(9090)
+}
+
+fn get_default_timeout() -> u64 {
+    30
+}
+
+#[derive(Deserialize, Serialize, Clone, Debug, PartialEq, Eq, PartialOrd, Ord)]
 pub enum Resolution {
     Sd,
     Hd720p,
@@ -54,7 +263,22 @@ pub enum Resolution {
     Uhd8k,
 }
 
-#[derive(Serialize, Clone, Debug)]
+impl std::str::FromStr for Resolution {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "Sd" => Ok(Resolution::Sd),
+            "Hd720p" => Ok(Resolution::Hd720p),
+            "Hd1080p" => Ok(Resolution::Hd1080p),
+            "Uhd4k" => Ok(Resolution::Uhd4k),
+            "Uhd8k" => Ok(Resolution::Uhd8k),
+            _ => Err(format!("unknown resolution {}", s)),
+        }
+    }
+}
+
+#[derive(Deserialize, Serialize, Clone, Debug, PartialEq)]
 pub struct Movie {
     pub id: u16,
     pub title: String,
@@ -63,6 +287,12 @@ pub struct Movie {
     pub premiered: String,
     pub resolution: Option<Resolution>,
     pub poster: Option<String>,
+    #[serde(default)]
+    pub fanart: Option<String>,
+    #[serde(default)]
+    pub set_poster: Option<String>,
+    #[serde(default)]
+    pub set_fanart: Option<String>,
     pub rating: f32,
     pub playcount: u8,
     pub set: Option<String>,
@@ -70,15 +300,234 @@ pub struct Movie {
     pub tags: Vec<String>,
     pub genres: Vec<String>,
     pub cast: Vec<Cast>,
+    #[serde(default)]
+    pub plot: String,
+    #[serde(default)]
+    pub video_codec: Option<String>,
+    /// HDR type, only known with Kodi 19 and later
+    #[serde(default)]
+    pub hdr: Option<String>,
+    #[serde(default)]
+    pub stereomode: Option<String>,
+    #[serde(default)]
+    pub aspect: Option<f32>,
+    #[serde(default)]
+    pub audio: Vec<AudioStream>,
+    #[serde(default)]
+    pub subtitle_languages: Vec<String>,
+}
+
+#[derive(Deserialize, Serialize, Clone, Debug, PartialEq)]
+pub struct AudioStream {
+    pub channels: u8,
+    pub codec: String,
+    pub language: Option<String>,
+}
+
+/// Filter on the quality of movies, every field set must match. Text is compared ignoring case.
+#[derive(Deserialize, Clone, Debug, Default)]
+#[serde(default)]
+pub struct MovieFilter {
+    pub resolution: Option<Resolution>,
+    pub video_codec: Option<String>,
+    /// HDR type, `none` for movies without HDR
+    pub hdr: Option<String>,
+    pub stereomode: Option<String>,
+    pub min_aspect: Option<f32>,
+    pub max_aspect: Option<f32>,
+    /// Minimum number of channels of the best audio stream
+    pub min_channels: Option<u8>,
+    pub audio_codec: Option<String>,
+    pub audio_language: Option<String>,
+    pub subtitle_language: Option<String>,
 }
 
+impl MovieFilter {
+    pub fn matches(&self, movie: &Movie) -> bool {
+        fn same(expected: &Option<String>, value: Option<&String>) -> bool {
+            expected.as_ref().is_none_or(|expected| {
+                value.is_some_and(|value| value.eq_ignore_ascii_case(expected))
+            })
+        }
+        fn any<'a>(
+            expected: &Option<String>,
+            mut values: impl Iterator<Item = &'a String>,
+        ) -> bool {
+            expected
+                .as_ref()
+                .is_none_or(|expected| values.any(|value| value.eq_ignore_ascii_case(expected)))
+        }
+
+        self.resolution
+            .as_ref()
+            .is_none_or(|resolution| movie.resolution.as_ref() == Some(resolution))
+            && same(&self.video_codec, movie.video_codec.as_ref())
+            && match self.hdr.as_deref() {
+                Some(hdr) if hdr.eq_ignore_ascii_case("none") => movie.hdr.is_none(),
+                _ => same(&self.hdr, movie.hdr.as_ref()),
+            }
+            && same(&self.stereomode, movie.stereomode.as_ref())
+            && self
+                .min_aspect
+                .is_none_or(|min| movie.aspect.is_some_and(|aspect| aspect >= min))
+            && self
+                .max_aspect
+                .is_none_or(|max| movie.aspect.is_some_and(|aspect| aspect <= max))
+            && self
+                .min_channels
+                .is_none_or(|min| movie.max_channels().is_some_and(|channels| channels >= min))
+            && any(
+                &self.audio_codec,
+                movie.audio.iter().map(|audio| &audio.codec),
+            )
+            && any(
+                &self.audio_language,
+                movie
+                    .audio
+                    .iter()
+                    .filter_map(|audio| audio.language.as_ref()),
+            )
+            && any(&self.subtitle_language, movie.subtitle_languages.iter())
+    }
+}
+
+impl Movie {
+    /// Number of channels of the audio stream with the most channels
+    pub fn max_channels(&self) -> Option<u8> {
+        self.audio.iter().map(|audio| audio.channels).max()
+    }
+}
+
+/// Movie lists of every Kodi instance, keyed by `Kodi::name`
+pub type MovieLists = std::collections::HashMap<String, Vec<Movie>>;
+
+/// A movie as seen across all Kodi instances
 #[derive(Serialize, Clone, Debug)]
+pub struct MergedMovie {
+    #[serde(flatten)]
+    pub movie: Movie,
+    pub instances: Vec<MovieInstance>,
+}
+
+/// A Kodi instance knowing a movie, with the movie id on this instance
+#[derive(Serialize, Clone, Debug)]
+pub struct MovieInstance {
+    pub kodi: String,
+    pub id: u16,
+}
+
+/// A movie updated on a Kodi instance by a sync, with the error if the update failed
+#[derive(Serialize, Clone, Debug)]
+pub struct SyncResult {
+    #[serde(flatten)]
+    pub instance: MovieInstance,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+#[derive(Deserialize, Serialize, Clone, Debug, PartialEq)]
 pub struct Cast {
     name: String,
     role: String,
     thumbnail: Option<String>,
 }
 
+#[derive(Serialize, Clone, Debug)]
+pub struct TvShow {
+    pub id: u16,
+    pub title: String,
+    pub path: String,
+    pub premiered: String,
+    pub poster: Option<String>,
+    pub rating: f32,
+    pub episode_count: u16,
+    pub watched_episodes: u16,
+    pub tags: Vec<String>,
+    pub genres: Vec<String>,
+    pub seasons: Vec<Season>,
+}
+
+#[derive(Serialize, Clone, Debug)]
+pub struct Season {
+    pub id: u32,
+    pub tvshow_id: u16,
+    pub season: u16,
+    pub poster: Option<String>,
+    pub episode_count: u16,
+    pub watched_episodes: u16,
+    pub episodes: Vec<Episode>,
+}
+
+#[derive(Serialize, Clone, Debug)]
+pub struct Episode {
+    pub id: u32,
+    pub tvshow_id: u16,
+    pub season: u16,
+    pub episode: u16,
+    pub title: String,
+    pub path: String,
+    pub firstaired: String,
+    pub runtime: u16,
+    pub rating: f32,
+    pub playcount: u8,
+}
+
+/// TV show lists of every Kodi instance, keyed by `Kodi::name`
+pub type TvShowLists = std::collections::HashMap<String, Vec<TvShow>>;
+
+/// Episodes missing from a season, based on the episode numbers already in the library
+#[derive(Serialize, Clone, Debug)]
+pub struct MissingEpisodes {
+    pub tvshow_id: u16,
+    pub title: String,
+    pub season: u16,
+    pub episodes: Vec<u16>,
+}
+
+#[derive(Serialize, Clone, Debug)]
+pub struct Artist {
+    pub id: u32,
+    pub name: String,
+    pub genres: Vec<String>,
+    pub thumbnail: Option<String>,
+}
+
+#[derive(Serialize, Clone, Debug)]
+pub struct Album {
+    pub id: u32,
+    pub title: String,
+    pub artists: Vec<String>,
+    pub artist_ids: Vec<u32>,
+    pub year: u16,
+    pub genres: Vec<String>,
+    pub thumbnail: Option<String>,
+    pub playcount: u16,
+}
+
+#[derive(Serialize, Clone, Debug)]
+pub struct Song {
+    pub id: u32,
+    pub title: String,
+    pub artists: Vec<String>,
+    pub album: Option<String>,
+    pub album_id: Option<u32>,
+    pub track: u16,
+    pub duration: u32,
+    pub path: String,
+    pub year: u16,
+    pub playcount: u16,
+}
+
+#[derive(Serialize, Clone, Debug, Default)]
+pub struct MusicLibrary {
+    pub artists: Vec<Artist>,
+    pub albums: Vec<Album>,
+    pub songs: Vec<Song>,
+}
+
+/// Music libraries of every Kodi instance, keyed by `Kodi::name`
+pub type MusicLibraries = std::collections::HashMap<String, MusicLibrary>;
+
 #[derive(Serialize, Clone, Debug)]
 pub struct File {
     pub path: String,
@@ -104,7 +553,7 @@ pub fn movie_list_cleanup(movie_list: Vec<Movie>) -> Vec<Movie> {
         .into_iter()
         .map(|mut movie| {
             if let Some(set) = movie.set.as_ref() {
-                if !set_list.contains(&&set) {
+                if !set_list.contains(set) {
                     movie.set = None;
                 }
             }
@@ -113,18 +562,609 @@ pub fn movie_list_cleanup(movie_list: Vec<Movie>) -> Vec<Movie> {
         .collect::<Vec<_>>()
 }
 
-#[instrument(skip(movie_list), level = "info")]
+/// Merge the movie lists of all Kodi instances, matching movies by title and premiered date
+pub fn merge_movie_lists(movie_lists: &MovieLists, kodis: &[Kodi]) -> Vec<MergedMovie> {
+    let mut merged: Vec<MergedMovie> = vec![];
+    let mut index: std::collections::HashMap<(String, String), usize> =
+        std::collections::HashMap::new();
+
+    for kodi in kodis {
+        for movie in movie_lists.get(&kodi.name).into_iter().flatten() {
+            let instance = MovieInstance {
+                kodi: kodi.name.clone(),
+                id: movie.id,
+            };
+            match index.entry((movie.title.clone(), movie.premiered.clone())) {
+                std::collections::hash_map::Entry::Occupied(entry) => {
+                    merged[*entry.get()].instances.push(instance)
+                }
+                std::collections::hash_map::Entry::Vacant(entry) => {
+                    entry.insert(merged.len());
+                    merged.push(MergedMovie {
+                        movie: movie.clone(),
+                        instances: vec![instance],
+                    });
+                }
+            }
+        }
+    }
+
+    merged.sort_by(|a, b| a.movie.title.cmp(&b.movie.title));
+    merged
+}
+
+/// A movie that is not in the same state on every Kodi instance
+#[derive(Serialize, Clone, Debug)]
+pub struct MovieDivergence {
+    pub title: String,
+    pub premiered: String,
+    /// Kodi instances not knowing the movie
+    pub missing: Vec<String>,
+    /// Fields with different values between instances
+    pub differences: Vec<String>,
+    /// The movie as known by each instance
+    pub movies: std::collections::BTreeMap<String, Movie>,
+}
+
+fn differs<T: PartialEq>(
+    movies: &std::collections::BTreeMap<String, Movie>,
+    field: impl Fn(&Movie) -> T,
+) -> bool {
+    let mut values = movies.values().map(field);
+    match values.next() {
+        Some(first) => values.any(|value| value != first),
+        None => false,
+    }
+}
+
+fn sorted_tags(movie: &Movie) -> Vec<String> {
+    let mut tags = movie.tags.clone();
+    tags.sort();
+    tags
+}
+
+/// List movies that are missing from an instance or that have different tags, play count or set
+/// between instances
+pub fn find_divergences(movie_lists: &MovieLists, kodis: &[Kodi]) -> Vec<MovieDivergence> {
+    merge_movie_lists(movie_lists, kodis)
+        .into_iter()
+        .filter_map(|merged| {
+            let movies = merged
+                .instances
+                .iter()
+                .filter_map(|instance| {
+                    movie_lists
+                        .get(&instance.kodi)?
+                        .iter()
+                        .find(|movie| movie.id == instance.id)
+                        .map(|movie| (instance.kodi.clone(), movie.clone()))
+                })
+                .collect::<std::collections::BTreeMap<_, _>>();
+
+            let missing = kodis
+                .iter()
+                .filter(|kodi| !movies.contains_key(&kodi.name))
+                .map(|kodi| kodi.name.clone())
+                .collect::<Vec<_>>();
+            let mut differences = vec![];
+            if differs(&movies, sorted_tags) {
+                differences.push("tags".to_string());
+            }
+            if differs(&movies, |movie| movie.playcount) {
+                differences.push("playcount".to_string());
+            }
+            if differs(&movies, |movie| movie.set.clone()) {
+                differences.push("set".to_string());
+            }
+
+            if missing.is_empty() && differences.is_empty() {
+                None
+            } else {
+                Some(MovieDivergence {
+                    title: merged.movie.title,
+                    premiered: merged.movie.premiered,
+                    missing,
+                    differences,
+                    movies,
+                })
+            }
+        })
+        .collect()
+}
+
+/// Push tags and watched state of the movies known by `source` to all other Kodi instances,
+/// returning every movie to update with the error if it could not be. An instance failing doesn't
+/// stop the others from being updated.
+#[instrument(skip(movie_lists, kodi_clients), level = "info")]
+pub async fn sync_movie_details(
+    movie_lists: actix_web::web::Data<std::sync::RwLock<MovieLists>>,
+    kodi_clients: actix_web::web::Data<std::sync::RwLock<kodi_rpc::KodiClients>>,
+    source: &Kodi,
+    kodis: &[Kodi],
+) -> Vec<SyncResult> {
+    let updates = find_divergences(&movie_lists.read().unwrap(), kodis)
+        .into_iter()
+        .filter_map(|divergence| {
+            let source_movie = divergence.movies.get(&source.name)?.clone();
+            Some(
+                divergence
+                    .movies
+                    .into_iter()
+                    .filter(|(kodi, movie)| {
+                        kodi != &source.name
+                            && (sorted_tags(movie) != sorted_tags(&source_movie)
+                                || movie.playcount != source_movie.playcount)
+                    })
+                    .map(|(kodi, movie)| (kodi, movie.id, source_movie.clone()))
+                    .collect::<Vec<_>>(),
+            )
+        })
+        .flatten()
+        .collect::<Vec<_>>();
+
+    let mut results = vec![];
+    for (kodi_name, movie_id, source_movie) in updates {
+        if let Some(kodi) = kodis.iter().find(|kodi| kodi.name == kodi_name) {
+            let instance = MovieInstance {
+                kodi: kodi_name.clone(),
+                id: movie_id,
+            };
+            let kodi_rpc = kodi_clients.read().unwrap().rpc(kodi);
+            let updated = match kodi_rpc {
+                Ok(kodi_rpc) => {
+                    kodi_rpc
+                        .set_movie_details(
+                            movie_id,
+                            kodi_rpc::MovieDetails {
+                                tag: Some(source_movie.tags.clone()),
+                                playcount: Some(source_movie.playcount),
+                                ..Default::default()
+                            },
+                        )
+                        .await
+                }
+                Err(err) => Err(err),
+            };
+            if let Err(err) = updated {
+                event!(
+                    Level::WARN,
+                    "can't sync {} on {}: {}",
+                    movie_id,
+                    kodi_name,
+                    err
+                );
+                results.push(SyncResult {
+                    instance,
+                    error: Some(err.to_string()),
+                });
+                continue;
+            }
+
+            if let Some(movie_list) = movie_lists.write().unwrap().get_mut(&kodi_name) {
+                movie_list
+                    .iter_mut()
+                    .filter(|movie| movie.id == movie_id)
+                    .for_each(|movie| {
+                        movie.tags = source_movie.tags.clone();
+                        movie.playcount = source_movie.playcount;
+                    });
+            }
+            events::EVENTS.send(events::Event::TagsUpdated {
+                kodi: kodi_name.clone(),
+                movie_id,
+                tags: source_movie.tags.clone(),
+            });
+            results.push(SyncResult {
+                instance,
+                error: None,
+            });
+        }
+    }
+    event!(
+        Level::INFO,
+        "synced movies: {}, failed: {}",
+        results
+            .iter()
+            .filter(|result| result.error.is_none())
+            .count(),
+        results
+            .iter()
+            .filter(|result| result.error.is_some())
+            .count()
+    );
+
+    results
+}
+
+/// Update the movie list of a Kodi instance from its current state, recording the changes in the
+/// history, then schedule a save of the cache. The update runs under the write lock, so that a
+/// concurrent refresh or notification is not overwritten.
+fn store_kodi_movie_list(
+    movie_lists: &actix_web::web::Data<std::sync::RwLock<MovieLists>>,
+    history: &actix_web::web::Data<std::sync::RwLock<cache::History>>,
+    kodi: &Kodi,
+    update: impl FnOnce(Vec<Movie>) -> Vec<Movie>,
+) {
+    {
+        let mut writable_movie_lists = movie_lists.write().unwrap();
+        let mut writable_history = history.write().unwrap();
+
+        let current_movie_list = writable_movie_lists
+            .get(&kodi.name)
+            .cloned()
+            .unwrap_or_default();
+        let new_movie_list = movie_list_cleanup(update(current_movie_list));
+        if let Some(entry) = writable_movie_lists
+            .get(&kodi.name)
+            .and_then(|old_movie_list| {
+                cache::diff_movie_lists(&kodi.name, old_movie_list, &new_movie_list)
+            })
+        {
+            cache::record(&mut writable_history, entry);
+        }
+        writable_movie_lists.insert(kodi.name.clone(), new_movie_list);
+    }
+    events::EVENTS.send(events::Event::MovieListRefreshed {
+        kodi: kodi.name.clone(),
+    });
+
+    actix_rt::spawn(save_cache(movie_lists.clone(), history.clone()));
+}
+
+/// Save the movie lists and the history to the cache file, if there is one, once no change came
+/// for [`CACHE_SAVE_DELAY`]. Only one save waits at a time, later changes are saved with it. The
+/// file is written off the async executor, once the locks are released.
+async fn save_cache(
+    movie_lists: actix_web::web::Data<std::sync::RwLock<MovieLists>>,
+    history: actix_web::web::Data<std::sync::RwLock<cache::History>>,
+) {
+    if CACHE_SAVE_SCHEDULED.swap(true, std::sync::atomic::Ordering::SeqCst) {
+        return;
+    }
+    actix_rt::time::delay_for(CACHE_SAVE_DELAY).await;
+    // a change from now on needs another save
+    CACHE_SAVE_SCHEDULED.store(false, std::sync::atomic::Ordering::SeqCst);
+
+    let cache_file = match CONFIG.read().unwrap().cache_file.clone() {
+        Some(cache_file) => cache_file,
+        None => return,
+    };
+    let snapshot = cache::Snapshot {
+        movies: movie_lists.read().unwrap().clone(),
+        history: history.read().unwrap().clone(),
+    };
+    let path = cache_file.clone();
+    if let Err(err) =
+        actix_web::web::block(move || cache::save(&path, &snapshot).map_err(|err| err.to_string()))
+            .await
+    {
+        event!(
+            Level::WARN,
+            "can't save the cache to {}: {}",
+            cache_file,
+            err
+        );
+    }
+}
+
+#[instrument(skip(movie_lists, history, kodi_clients), level = "info")]
+pub async fn update_kodi_movie_list(
+    movie_lists: actix_web::web::Data<std::sync::RwLock<MovieLists>>,
+    history: actix_web::web::Data<std::sync::RwLock<cache::History>>,
+    kodi_clients: actix_web::web::Data<std::sync::RwLock<kodi_rpc::KodiClients>>,
+    kodi: &Kodi,
+) -> actix_web::web::Data<std::sync::RwLock<MovieLists>> {
+    let kodi_rpc = kodi_clients.read().unwrap().rpc(kodi);
+    if let Ok(new_movie_list) = async { kodi_rpc?.get_all_movies().await }.await {
+        store_kodi_movie_list(&movie_lists, &history, kodi, |_| new_movie_list);
+    }
+
+    movie_lists
+}
+
+/// Update the movie list of a Kodi instance after a notification
+#[instrument(skip(movie_lists, history, kodi_clients), level = "info")]
+async fn apply_notification(
+    movie_lists: actix_web::web::Data<std::sync::RwLock<MovieLists>>,
+    history: actix_web::web::Data<std::sync::RwLock<cache::History>>,
+    kodi_clients: actix_web::web::Data<std::sync::RwLock<kodi_rpc::KodiClients>>,
+    kodi: &Kodi,
+    notification: kodi_rpc::Notification,
+) {
+    match notification {
+        kodi_rpc::Notification::VideoUpdated { item_type, id } if item_type == "movie" => {
+            let kodi_rpc = kodi_clients.read().unwrap().rpc(kodi);
+            if let Ok(movie) = async { kodi_rpc?.get_movie_details(id).await }.await {
+                // movies alone in their set are stored without it, so a movie joining one of
+                // them can't be patched in: the whole list is fetched again, as on a refresh
+                let joins_lone_set = movie.set.as_ref().is_some_and(|set| {
+                    !movie_lists
+                        .read()
+                        .unwrap()
+                        .get(&kodi.name)
+                        .into_iter()
+                        .flatten()
+                        .any(|known| known.id != id && known.set.as_ref() == Some(set))
+                });
+                if joins_lone_set {
+                    update_kodi_movie_list(movie_lists, history, kodi_clients, kodi).await;
+                } else {
+                    store_kodi_movie_list(&movie_lists, &history, kodi, |mut movie_list| {
+                        movie_list.retain(|known| known.id != id);
+                        movie_list.push(movie);
+                        movie_list.sort_by(|a, b| a.title.cmp(&b.title));
+                        movie_list
+                    });
+                }
+            }
+        }
+        kodi_rpc::Notification::VideoRemoved { item_type, id } if item_type == "movie" => {
+            store_kodi_movie_list(&movie_lists, &history, kodi, |mut movie_list| {
+                movie_list.retain(|known| known.id != id);
+                movie_list
+            });
+        }
+        kodi_rpc::Notification::VideoScanFinished | kodi_rpc::Notification::VideoCleanFinished => {
+            update_kodi_movie_list(movie_lists, history, kodi_clients, kodi).await;
+        }
+        _ => (),
+    }
+}
+
+/// Keep the movie list of a Kodi instance up to date from its notifications, reconnecting when
+/// the connection is lost
+#[instrument(skip(movie_lists, history, kodi_clients), level = "info")]
+pub async fn follow_kodi_notifications(
+    movie_lists: actix_web::web::Data<std::sync::RwLock<MovieLists>>,
+    history: actix_web::web::Data<std::sync::RwLock<cache::History>>,
+    kodi_clients: actix_web::web::Data<std::sync::RwLock<kodi_rpc::KodiClients>>,
+    kodi: Kodi,
+) {
+    let port = if let Some(port) = kodi.tcp_port {
+        port
+    } else {
+        return;
+    };
+
+    loop {
+        let kodi_rpc = kodi_clients.read().unwrap().rpc(&kodi);
+        if let Ok(notifications) = async { kodi_rpc?.notifications(port).await }.await {
+            futures::pin_mut!(notifications);
+            while let Some(notification) = futures::StreamExt::next(&mut notifications).await {
+                event!(Level::DEBUG, "received notification: {:?}", notification);
+                apply_notification(
+                    movie_lists.clone(),
+                    history.clone(),
+                    kodi_clients.clone(),
+                    &kodi,
+                    notification,
+                )
+                .await;
+            }
+            event!(Level::WARN, "lost connection to {}", kodi.name);
+        }
+        actix_rt::time::delay_for(std::time::Duration::from_secs(30)).await;
+    }
+}
+
+#[instrument(skip(movie_lists, history, kodi_clients), level = "info")]
 pub async fn update_movie_list(
-    movie_list: actix_web::web::Data<std::sync::RwLock<Vec<Movie>>>,
-) -> actix_web::web::Data<std::sync::RwLock<Vec<Movie>>> {
-    let config = CONFIG.read().unwrap();
+    movie_lists: actix_web::web::Data<std::sync::RwLock<MovieLists>>,
+    history: actix_web::web::Data<std::sync::RwLock<cache::History>>,
+    kodi_clients: actix_web::web::Data<std::sync::RwLock<kodi_rpc::KodiClients>>,
+) -> actix_web::web::Data<std::sync::RwLock<MovieLists>> {
+    let kodis = CONFIG.read().unwrap().kodis.clone();
+
+    futures::future::join_all(kodis.iter().map(|kodi| {
+        update_kodi_movie_list(
+            movie_lists.clone(),
+            history.clone(),
+            kodi_clients.clone(),
+            kodi,
+        )
+    }))
+    .await;
+
+    movie_lists
+}
 
-    if let Ok(new_movie_list) = kodi_rpc::KodiRPC::new(&config.kodis[0].url)
-        .get_all_movies()
-        .await
+/// Put seasons in their TV show, and episodes in their season
+pub fn tvshow_list_assemble(
+    tvshows: Vec<TvShow>,
+    seasons: Vec<Season>,
+    episodes: Vec<Episode>,
+) -> Vec<TvShow> {
+    let mut episodes_by_season =
+        episodes
+            .into_iter()
+            .fold(std::collections::HashMap::new(), |mut map, episode| {
+                map.entry((episode.tvshow_id, episode.season))
+                    .or_insert_with(Vec::new)
+                    .push(episode);
+                map
+            });
+    let mut seasons_by_tvshow =
+        seasons
+            .into_iter()
+            .fold(std::collections::HashMap::new(), |mut map, mut season| {
+                season.episodes = episodes_by_season
+                    .remove(&(season.tvshow_id, season.season))
+                    .unwrap_or_default();
+                map.entry(season.tvshow_id)
+                    .or_insert_with(Vec::new)
+                    .push(season);
+                map
+            });
+
+    tvshows
+        .into_iter()
+        .map(|mut tvshow| {
+            tvshow.seasons = seasons_by_tvshow.remove(&tvshow.id).unwrap_or_default();
+            tvshow.seasons.sort_by_key(|season| season.season);
+            tvshow
+        })
+        .collect()
+}
+
+#[instrument(skip(tvshow_lists, kodi_clients), level = "info")]
+pub async fn update_kodi_tvshow_list(
+    tvshow_lists: actix_web::web::Data<std::sync::RwLock<TvShowLists>>,
+    kodi_clients: actix_web::web::Data<std::sync::RwLock<kodi_rpc::KodiClients>>,
+    kodi: &Kodi,
+) -> actix_web::web::Data<std::sync::RwLock<TvShowLists>> {
+    let kodi_rpc = match kodi_clients.read().unwrap().rpc(kodi) {
+        Ok(kodi_rpc) => kodi_rpc,
+        Err(_) => return tvshow_lists,
+    };
+    if let (Ok(tvshows), Ok(seasons), Ok(episodes)) = futures::future::join3(
+        kodi_rpc.get_all_tvshows(),
+        kodi_rpc.get_seasons(None),
+        kodi_rpc.get_episodes(None),
+    )
+    .await
     {
-        *movie_list.write().unwrap() = movie_list_cleanup(new_movie_list);
+        tvshow_lists.write().unwrap().insert(
+            kodi.name.clone(),
+            tvshow_list_assemble(tvshows, seasons, episodes),
+        );
     }
 
-    movie_list
+    tvshow_lists
+}
+
+#[instrument(skip(tvshow_lists, kodi_clients), level = "info")]
+pub async fn update_tvshow_list(
+    tvshow_lists: actix_web::web::Data<std::sync::RwLock<TvShowLists>>,
+    kodi_clients: actix_web::web::Data<std::sync::RwLock<kodi_rpc::KodiClients>>,
+) -> actix_web::web::Data<std::sync::RwLock<TvShowLists>> {
+    let kodis = CONFIG.read().unwrap().kodis.clone();
+
+    futures::future::join_all(
+        kodis
+            .iter()
+            .map(|kodi| update_kodi_tvshow_list(tvshow_lists.clone(), kodi_clients.clone(), kodi)),
+    )
+    .await;
+
+    tvshow_lists
+}
+
+#[instrument(skip(music_libraries, kodi_clients), level = "info")]
+pub async fn update_kodi_music_library(
+    music_libraries: actix_web::web::Data<std::sync::RwLock<MusicLibraries>>,
+    kodi_clients: actix_web::web::Data<std::sync::RwLock<kodi_rpc::KodiClients>>,
+    kodi: &Kodi,
+) -> actix_web::web::Data<std::sync::RwLock<MusicLibraries>> {
+    let kodi_rpc = match kodi_clients.read().unwrap().rpc(kodi) {
+        Ok(kodi_rpc) => kodi_rpc,
+        Err(_) => return music_libraries,
+    };
+    if let (Ok(artists), Ok(albums), Ok(songs)) = futures::future::join3(
+        kodi_rpc.get_all_artists(),
+        kodi_rpc.get_all_albums(),
+        kodi_rpc.get_all_songs(),
+    )
+    .await
+    {
+        music_libraries.write().unwrap().insert(
+            kodi.name.clone(),
+            MusicLibrary {
+                artists,
+                albums,
+                songs,
+            },
+        );
+    }
+
+    music_libraries
+}
+
+#[instrument(skip(music_libraries, kodi_clients), level = "info")]
+pub async fn update_music_library(
+    music_libraries: actix_web::web::Data<std::sync::RwLock<MusicLibraries>>,
+    kodi_clients: actix_web::web::Data<std::sync::RwLock<kodi_rpc::KodiClients>>,
+) -> actix_web::web::Data<std::sync::RwLock<MusicLibraries>> {
+    let kodis = CONFIG.read().unwrap().kodis.clone();
+
+    futures::future::join_all(kodis.iter().map(|kodi| {
+        update_kodi_music_library(music_libraries.clone(), kodi_clients.clone(), kodi)
+    }))
+    .await;
+
+    music_libraries
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+
+    /// A movie with `fields` set over the fields of an empty movie
+    pub fn movie(fields: serde_json::Value) -> Movie {
+        let mut value = serde_json::json!({
+            "id": 1,
+            "title": "Alien",
+            "runtime": 117,
+            "path": "/movies/Alien 1979.mkv",
+            "premiered": "1979-05-25",
+            "rating": 8.0,
+            "playcount": 0,
+            "dateadded": "2020-01-01 00:00:00",
+            "tags": [],
+            "genres": [],
+            "cast": [],
+        });
+        for (key, field) in fields.as_object().unwrap() {
+            value[key] = field.clone();
+        }
+        serde_json::from_value(value).unwrap()
+    }
+
+    #[test]
+    fn filter_table() {
+        let uhd_hdr = movie(serde_json::json!({
+            "resolution": "Uhd4k",
+            "video_codec": "hevc",
+            "hdr": "dolbyvision",
+            "aspect": 2.39,
+            "audio": [
+                { "channels": 8, "codec": "truehd", "language": "eng" },
+                { "channels": 2, "codec": "aac", "language": "fre" },
+            ],
+            "subtitle_languages": ["fre"],
+        }));
+        let sd = movie(serde_json::json!({
+            "resolution": "Sd",
+            "video_codec": "mpeg4",
+            "aspect": 1.33,
+            "audio": [{ "channels": 2, "codec": "mp3", "language": null }],
+        }));
+        let unknown = movie(serde_json::json!({}));
+
+        // query, and whether it matches each movie
+        let table: &[(&str, [bool; 3])] = &[
+            ("", [true, true, true]),
+            ("resolution=Uhd4k", [true, false, false]),
+            ("video_codec=HEVC", [true, false, false]),
+            ("hdr=DolbyVision", [true, false, false]),
+            ("hdr=none", [false, true, true]),
+            ("hdr=None", [false, true, true]),
+            ("min_aspect=2", [true, false, false]),
+            ("max_aspect=2", [false, true, false]),
+            ("min_channels=6", [true, false, false]),
+            ("min_channels=2", [true, true, false]),
+            ("audio_codec=aac", [true, false, false]),
+            ("audio_language=fre", [true, false, false]),
+            ("subtitle_language=FRE", [true, false, false]),
+            ("resolution=Uhd4k&audio_codec=mp3", [false, false, false]),
+        ];
+        for (query, expected) in table {
+            let filter = actix_web::web::Query::<MovieFilter>::from_query(query).unwrap();
+            let matches = [
+                filter.matches(&uhd_hdr),
+                filter.matches(&sd),
+                filter.matches(&unknown),
+            ];
+            assert_eq!(&matches, expected, "{}", query);
+        }
+    }
 }
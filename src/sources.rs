@@ -0,0 +1,107 @@
+use serde::{Deserialize, Serialize};
+
+use crate::Config;
+
+/// A folder with movies
+#[derive(Deserialize, Serialize, Clone, Debug)]
+pub struct MovieSource {
+    /// The folder as seen by Kodi
+    pub path: String,
+    /// Levels of subfolders to look into for movie files, 0 for files directly in the folder
+    #[serde(default)]
+    pub depth: u8,
+    /// Other prefixes of this folder in the paths known by Kodi, like `nfs://nas/volume1/movies/`
+    /// for a `smb://nas/movies/` source
+    #[serde(default)]
+    pub aliases: Vec<String>,
+    /// Patterns of files to ignore in this folder, with `filepatterns_to_ignore`
+    #[serde(default)]
+    pub filepatterns_to_ignore: Vec<String>,
+    /// Patterns finding the title and year of movies in this folder, before `movie_patterns`
+    #[serde(default)]
+    pub movie_patterns: Vec<String>,
+}
+
+/// All the movie sources, the legacy `movies_directory` first
+pub fn movie_sources(config: &Config) -> Vec<MovieSource> {
+    config
+        .movies_directory
+        .iter()
+        .map(|path| MovieSource {
+            path: path.clone(),
+            depth: 0,
+            aliases: vec![],
+            filepatterns_to_ignore: vec![],
+            movie_patterns: vec![],
+        })
+        .chain(config.movie_sources.iter().cloned())
+        .collect()
+}
+
+/// Normalize a path so that different writings of the same file can be compared: backslashes
+/// become slashes, the scheme is lowercased and credentials are removed
+pub fn normalize_path(path: &str) -> String {
+    let path = path.replace('\\', "/");
+    match path.split_once("://") {
+        Some((scheme, rest)) => {
+            let rest = match rest.split_once('/') {
+                Some((authority, rest)) => format!(
+                    "{}/{}",
+                    authority.rsplit('@').next().unwrap_or(authority),
+                    rest
+                ),
+                None => rest.rsplit('@').next().unwrap_or(rest).to_string(),
+            };
+            format!("{}://{}", scheme.to_lowercase(), rest)
+        }
+        None => path,
+    }
+}
+
+/// Movie sources with their normalized prefixes, to find the source of a path
+#[derive(Clone, Debug)]
+pub struct Sources {
+    sources: Vec<(MovieSource, String, Vec<String>)>,
+}
+
+impl Sources {
+    pub fn from_config(config: &Config) -> Sources {
+        Sources {
+            sources: movie_sources(config)
+                .into_iter()
+                .map(|source| {
+                    let prefix = normalize_path(&source.path);
+                    let aliases = source
+                        .aliases
+                        .iter()
+                        .map(|alias| normalize_path(alias))
+                        .collect();
+                    (source, prefix, aliases)
+                })
+                .collect(),
+        }
+    }
+
+    /// Normalize a path, replacing the alias of a source by its path
+    pub fn normalize(&self, path: &str) -> String {
+        let path = normalize_path(path);
+        self.sources
+            .iter()
+            .find_map(|(_, prefix, aliases)| {
+                aliases.iter().find_map(|alias| {
+                    path.strip_prefix(alias.as_str())
+                        .map(|rest| format!("{}{}", prefix, rest))
+                })
+            })
+            .unwrap_or(path)
+    }
+
+    /// The source a path is in, with the normalized path relative to it
+    pub fn find(&self, path: &str) -> Option<(&MovieSource, String)> {
+        let path = self.normalize(path);
+        self.sources.iter().find_map(|(source, prefix, _)| {
+            path.strip_prefix(prefix.as_str())
+                .map(|relative| (source, relative.to_string()))
+        })
+    }
+}
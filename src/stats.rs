@@ -0,0 +1,122 @@
+use serde::Serialize;
+
+use crate::Movie;
+
+/// Number of movies for each value
+pub type Breakdown = std::collections::BTreeMap<String, usize>;
+
+/// Quality of the movies of a library
+#[derive(Serialize, Clone, Debug)]
+pub struct QualityStats {
+    pub total: usize,
+    pub video_codecs: Breakdown,
+    pub resolutions: Breakdown,
+    pub hdr: Breakdown,
+    /// Layout of the best audio stream of each movie
+    pub audio_layouts: Breakdown,
+}
+
+const UNKNOWN: &str = "unknown";
+
+/// Usual name of a number of audio channels
+pub fn audio_layout(channels: u8) -> String {
+    match channels {
+        1 => "mono".to_string(),
+        2 => "stereo".to_string(),
+        6 => "5.1".to_string(),
+        8 => "7.1".to_string(),
+        channels => format!("{} channels", channels),
+    }
+}
+
+fn breakdown(values: impl Iterator<Item = Option<String>>) -> Breakdown {
+    values.fold(Breakdown::new(), |mut breakdown, value| {
+        *breakdown
+            .entry(value.unwrap_or_else(|| UNKNOWN.to_string()))
+            .or_insert(0) += 1;
+        breakdown
+    })
+}
+
+pub fn quality_stats(movies: &[Movie]) -> QualityStats {
+    QualityStats {
+        total: movies.len(),
+        video_codecs: breakdown(movies.iter().map(|movie| movie.video_codec.clone())),
+        resolutions: breakdown(movies.iter().map(|movie| {
+            movie
+                .resolution
+                .as_ref()
+                .map(|resolution| format!("{:?}", resolution))
+        })),
+        hdr: breakdown(
+            movies
+                .iter()
+                .map(|movie| Some(movie.hdr.clone().unwrap_or_else(|| "none".to_string()))),
+        ),
+        audio_layouts: breakdown(
+            movies
+                .iter()
+                .map(|movie| movie.max_channels().map(audio_layout)),
+        ),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tests::movie;
+
+    #[test]
+    fn quality_stats_breakdowns() {
+        let movies = vec![
+            movie(serde_json::json!({
+                "resolution": "Uhd4k",
+                "video_codec": "hevc",
+                "hdr": "hdr10",
+                "audio": [
+                    { "channels": 8, "codec": "truehd", "language": "eng" },
+                    { "channels": 2, "codec": "aac", "language": "eng" },
+                ],
+            })),
+            movie(serde_json::json!({
+                "resolution": "Hd1080p",
+                "video_codec": "h264",
+                "audio": [{ "channels": 6, "codec": "ac3", "language": "eng" }],
+            })),
+            movie(serde_json::json!({ "video_codec": "h264" })),
+        ];
+        let breakdown = |values: &[(&str, usize)]| {
+            values
+                .iter()
+                .map(|(value, count)| (value.to_string(), *count))
+                .collect::<Breakdown>()
+        };
+
+        let stats = quality_stats(&movies);
+        assert_eq!(stats.total, 3);
+        assert_eq!(stats.video_codecs, breakdown(&[("h264", 2), ("hevc", 1)]));
+        assert_eq!(
+            stats.resolutions,
+            breakdown(&[("Hd1080p", 1), ("Uhd4k", 1), (UNKNOWN, 1)])
+        );
+        assert_eq!(stats.hdr, breakdown(&[("hdr10", 1), ("none", 2)]));
+        assert_eq!(
+            stats.audio_layouts,
+            breakdown(&[("5.1", 1), ("7.1", 1), (UNKNOWN, 1)])
+        );
+    }
+
+    #[test]
+    fn audio_layouts() {
+        let table: &[(u8, &str)] = &[
+            (1, "mono"),
+            (2, "stereo"),
+            (6, "5.1"),
+            (8, "7.1"),
+            (3, "3 channels"),
+        ];
+        for (channels, layout) in table {
+            assert_eq!(audio_layout(*channels), *layout);
+        }
+    }
+}